@@ -3,19 +3,40 @@ use std::path;
 
 use crate::{
     commands::{
-        add::AddCommand, config::ConfigCommand, help::HelpCommand, init::InitCommand,
-        rm::RmCommand, status::StatusCommand,
+        add::AddCommand, branch::BranchCommand, cat_file::CatFileCommand,
+        check_ignore::CheckIgnoreCommand, checkout::CheckoutCommand, commit::CommitCommand,
+        config::ConfigCommand, diff::DiffCommand, fsck::FsckCommand, gc::GcCommand,
+        hash_object::HashObjectCommand, help::HelpCommand, init::InitCommand, log::LogCommand,
+        ls_files::LsFilesCommand, merge::MergeCommand, prune::PruneCommand, repack::RepackCommand,
+        rm::RmCommand, status::StatusCommand, switch::SwitchCommand, tag::TagCommand,
     },
+    config::{Config, CONFIG_KEYS},
     traits::{ExecutableCommand, FromString},
     utils::{get_current_dir, get_kvc_root_folder},
 };
 
 mod add;
+mod branch;
+mod cat_file;
+mod check_ignore;
+mod checkout;
+mod commit;
 mod config;
+mod diff;
+mod fsck;
+mod gc;
+mod hash_object;
 mod help;
 mod init;
+mod log;
+mod ls_files;
+mod merge;
+mod prune;
+mod repack;
 mod rm;
 mod status;
+mod switch;
+mod tag;
 
 #[derive(clap::ValueEnum, Clone, Debug, PartialEq)]
 pub enum Command {
@@ -25,6 +46,22 @@ pub enum Command {
     Add,
     Rm,
     Status,
+    Commit,
+    Repack,
+    Gc,
+    Prune,
+    Fsck,
+    HashObject,
+    CatFile,
+    LsFiles,
+    Log,
+    Branch,
+    Switch,
+    Checkout,
+    Merge,
+    Diff,
+    Tag,
+    CheckIgnore,
 }
 
 impl FromString for Command {
@@ -36,6 +73,22 @@ impl FromString for Command {
             "add" => Command::Add,
             "rm" => Command::Rm,
             "status" => Command::Status,
+            "commit" => Command::Commit,
+            "repack" => Command::Repack,
+            "gc" => Command::Gc,
+            "prune" => Command::Prune,
+            "fsck" => Command::Fsck,
+            "hash-object" => Command::HashObject,
+            "cat-file" => Command::CatFile,
+            "ls-files" => Command::LsFiles,
+            "log" => Command::Log,
+            "branch" => Command::Branch,
+            "switch" => Command::Switch,
+            "checkout" => Command::Checkout,
+            "merge" => Command::Merge,
+            "diff" => Command::Diff,
+            "tag" => Command::Tag,
+            "check-ignore" => Command::CheckIgnore,
             _ => Command::Help,
         }
     }
@@ -45,10 +98,43 @@ impl FromString for Command {
 pub struct Cli {
     #[clap(value_enum, default_value_t=Command::Help)]
     pub command: Command,
+    #[clap(trailing_var_arg = true, allow_hyphen_values = true)]
     pub args: Vec<String>,
 }
 
 impl Cli {
+    /// Splits the `-c key=value` pairs given before the command off the
+    /// process arguments. They are taken by hand since commands like `switch`
+    /// have a `-c` of their own.
+    pub fn take_config_overrides(args: Vec<String>) -> (Vec<String>, Vec<(String, String)>) {
+        let mut args = args.into_iter().peekable();
+        let mut cli_args: Vec<String> = args.next().into_iter().collect();
+        let mut overrides: Vec<(String, String)> = Vec::new();
+
+        while args.next_if(|v| v == "-c").is_some() {
+            let pair = args.next().unwrap_or_default();
+
+            match pair.split_once('=') {
+                Some((key, value)) if CONFIG_KEYS.contains(&key) => {
+                    overrides.push((key.to_owned(), value.to_owned()))
+                }
+                _ => {
+                    let invalid_override_msg = format!("Invalid config override {:?}.", pair)
+                        + "\n\n"
+                        + "Use -c key=value before the command, with one of: "
+                        + &CONFIG_KEYS.join(", ");
+
+                    println!("{}", invalid_override_msg);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        cli_args.extend(args);
+
+        (cli_args, overrides)
+    }
+
     fn get_executable(
         command: Command,
         args: Vec<String>,
@@ -61,22 +147,39 @@ impl Cli {
             Command::Add => Box::new(AddCommand::new(args, root_folder)),
             Command::Rm => Box::new(RmCommand::new(args, root_folder)),
             Command::Status => Box::new(StatusCommand::new(args, root_folder)),
+            Command::Commit => Box::new(CommitCommand::new(args, root_folder)),
+            Command::Repack => Box::new(RepackCommand::new(args, root_folder)),
+            Command::Gc => Box::new(GcCommand::new(args, root_folder)),
+            Command::Prune => Box::new(PruneCommand::new(args, root_folder)),
+            Command::Fsck => Box::new(FsckCommand::new(args, root_folder)),
+            Command::HashObject => Box::new(HashObjectCommand::new(args, root_folder)),
+            Command::CatFile => Box::new(CatFileCommand::new(args, root_folder)),
+            Command::LsFiles => Box::new(LsFilesCommand::new(args, root_folder)),
+            Command::Log => Box::new(LogCommand::new(args, root_folder)),
+            Command::Branch => Box::new(BranchCommand::new(args, root_folder)),
+            Command::Switch => Box::new(SwitchCommand::new(args, root_folder)),
+            Command::Checkout => Box::new(CheckoutCommand::new(args, root_folder)),
+            Command::Merge => Box::new(MergeCommand::new(args, root_folder)),
+            Command::Diff => Box::new(DiffCommand::new(args, root_folder)),
+            Command::Tag => Box::new(TagCommand::new(args, root_folder)),
+            Command::CheckIgnore => Box::new(CheckIgnoreCommand::new(args, root_folder)),
         }
     }
 
-    pub fn execute(command: Command, args: Vec<String>) {
+    pub fn execute(command: Command, args: Vec<String>, config_overrides: Vec<(String, String)>) {
+        Config::set_command_line_overrides(config_overrides);
+
         let cur_dir_path = get_current_dir();
         let (is_kvc_repo, root_folder) = get_kvc_root_folder(cur_dir_path);
 
-        let commands_that_outside_kvc_repo: [Command; 2] = [Command::Init, Command::Help];
-        if !commands_that_outside_kvc_repo.contains(&command) {
-            if !is_kvc_repo {
-                let not_kvc_repo_msg = "This is not a KVC repository!".to_owned()
-                    + "\n\nUse `kvc init` to start a repository here.";
+        let commands_that_outside_kvc_repo: [Command; 3] =
+            [Command::Init, Command::Help, Command::Config];
+        if !commands_that_outside_kvc_repo.contains(&command) && !is_kvc_repo {
+            let not_kvc_repo_msg = "This is not a KVC repository!".to_owned()
+                + "\n\nUse `kvc init` to start a repository here.";
 
-                println!("{}", not_kvc_repo_msg);
-                std::process::exit(1);
-            }
+            println!("{}", not_kvc_repo_msg);
+            std::process::exit(1);
         }
 
         let executable = Self::get_executable(command, args, root_folder);
@@ -1,27 +1,34 @@
 use std::{
     fs::{self},
-    io::Write,
     path, process,
 };
 
 use crate::{
     commands::ExecutableCommand,
-    constants::OBJECTS_FOLDER_NAME,
-    staging_area::StagingArea,
-    utils::{
-        generate_hash, get_file_path_relative_to_root, read_file, segment_hash, validate_path,
-    },
+    constants::ROOT_FOLDER_NAME,
+    ignore::IgnoreRules,
+    object::{write_blob_from_file, ObjectError},
+    parallel::{self, WalkEntry},
+    staging_area::{FileStat, StagingArea},
+    tree::is_storable_path,
+    utils::{get_file_path_relative_to_root, is_inside_current_dir, validate_path},
 };
 
 pub struct AddCommand {
     pub root_path: path::PathBuf,
     pub path: String,
+    pub force: bool,
+    pub all: bool,
 }
 
 impl ExecutableCommand for AddCommand {
     fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
-        let path = match args.first() {
+        let force = args.iter().any(|v| v == "-f" || v == "--force");
+        let all = args.iter().any(|v| v == "-A" || v == "--all");
+
+        let path = match args.iter().find(|v| !v.starts_with('-')) {
             Some(v) => v,
+            None if all => ".",
             None => {
                 println!("You must pass a path to add to the index");
                 std::process::exit(1);
@@ -31,123 +38,150 @@ impl ExecutableCommand for AddCommand {
         AddCommand {
             path: path.to_owned(),
             root_path: root_folder,
+            force,
+            all,
         }
     }
 
     fn run(&self) {
+        let relative_path =
+            get_file_path_relative_to_root(self.root_path.clone(), self.path.clone());
+
+        // The index is loaded once, updated in memory and written at the end.
+        let mut staging_area = StagingArea::lock(self.root_path.clone());
+
+        // With -A, a path that no longer exists stages the deletion of what
+        // was tracked under it.
+        if self.all
+            && !validate_path(self.path.clone())
+            && is_inside_current_dir(&self.path)
+            && !staging_area.remove_deleted(&relative_path).is_empty()
+        {
+            staging_area.save();
+            return;
+        }
+
         let valid_path = validate_path(self.path.clone());
         if !valid_path {
             println!("The provided path is not valid to add files to the index!");
+            // Exiting skips destructors, so the index lock is released first.
+            drop(staging_area);
             process::exit(1);
         }
 
         let path = &self.path;
         let file_metadata = fs::metadata(path).unwrap();
-
-        if file_metadata.is_file() {
-            self.process_file(path);
+        let ignore_rules = IgnoreRules::load(&self.root_path);
+
+        if !self.force
+            && ignore_rules.excludes(
+                &relative_path,
+                file_metadata.is_dir(),
+                staging_area.entries(),
+            )
+        {
+            let ignored_msg = format!(
+                "The path {} is ignored by one of your .kvcignore files.",
+                path
+            ) + "\n"
+                + "Use -f if you really want to add it.";
+
+            println!("{}", ignored_msg);
+            drop(staging_area);
             process::exit(1);
         }
 
-        self.process_dir(path);
-    }
-}
-
-impl AddCommand {
-    fn process_dir(&self, path: &str) {
-        let read_dir = match fs::read_dir(path) {
-            Ok(v) => v,
-            Err(_e) => panic!("Could not read directory!"),
+        let result = match file_metadata.is_file() {
+            true => self.process_file(path, &mut staging_area),
+            false => self.process_dir(path, &ignore_rules, &mut staging_area),
         };
 
-        for entry in read_dir {
-            let read_entry = entry.unwrap();
-            let entry_path = read_entry.path();
-            let entry_metadata = fs::metadata(&entry_path).unwrap();
-
-            let path = entry_path.to_str().unwrap();
-            if entry_metadata.is_file() {
-                self.process_file(path);
-                continue;
-            }
-
-            self.process_dir(path);
+        if let Err(e) = result {
+            drop(staging_area);
+            println!("Could not add {}: {}", path, e);
+            process::exit(1);
         }
-    }
-
-    fn process_file(&self, path: &str) {
-        let file_content = read_file(path);
-        let file_hash = match generate_hash(&file_content) {
-            Ok(v) => v,
-            Err(_e) => panic!("Could not generate file hash!"),
-        };
 
-        let (prefix, suffix) = segment_hash(&file_hash);
-
-        self.create_prefix_dir(prefix);
-
-        let mut file = self.create_suffix_file(prefix, suffix);
-
-        match file.write_all(file_content.as_bytes()) {
-            Ok(ok) => ok,
-            Err(e) => panic!("Error writing to config file: {}", e),
-        };
+        if self.all {
+            staging_area.remove_deleted(&relative_path);
+        }
 
-        self.add_file_to_index(path.to_owned(), file_hash);
+        staging_area.save();
     }
+}
 
-    fn add_file_to_index(&self, path: String, file_hash: String) {
-        let mut staging_area = StagingArea::open(self.root_path.clone());
+impl AddCommand {
+    /// Lists the files under `path` and hashes and compresses the changed
+    /// ones across the worker threads, adding them in path order.
+    fn process_dir(
+        &self,
+        path: &str,
+        ignore_rules: &IgnoreRules,
+        staging_area: &mut StagingArea,
+    ) -> Result<(), ObjectError> {
+        let relative_dir = get_file_path_relative_to_root(self.root_path.clone(), path.to_owned());
+        let threads = parallel::thread_count(&self.root_path);
+        let index = staging_area.entries();
+
+        let files = parallel::walk(
+            path::Path::new(path),
+            &relative_dir,
+            threads,
+            |relative_path, is_dir| {
+                relative_path.split('/').next() == Some(ROOT_FOLDER_NAME)
+                    || (!self.force && ignore_rules.excludes(relative_path, is_dir, index))
+            },
+        );
+
+        if let Some(file) = files.iter().find(|v| !is_storable_path(&v.relative_path)) {
+            return Err(ObjectError::UnsupportedPath(file.relative_path.clone()));
+        }
 
-        let file_path_from_root = get_file_path_relative_to_root(self.root_path.clone(), path);
+        let changed: Vec<WalkEntry> = files
+            .into_iter()
+            .filter(|v| !staging_area.is_unchanged(&v.relative_path, &v.metadata))
+            .collect();
+
+        let hashes = parallel::map(&changed, threads, |file| {
+            write_blob_from_file(&self.root_path, &file.path.to_string_lossy())
+        });
+
+        for (file, file_hash) in changed.into_iter().zip(hashes) {
+            staging_area.add(
+                file.relative_path,
+                file_hash?,
+                FileStat::from_metadata(&file.metadata),
+            );
+        }
 
-        staging_area.add(file_path_from_root, file_hash);
+        Ok(())
     }
 
-    fn create_prefix_dir(&self, prefix: &str) {
-        let path = self
-            .root_path
-            .clone()
-            .join(OBJECTS_FOLDER_NAME)
-            .join(prefix);
+    fn process_file(&self, path: &str, staging_area: &mut StagingArea) -> Result<(), ObjectError> {
+        let file_path_from_root =
+            get_file_path_relative_to_root(self.root_path.clone(), path.to_owned());
 
-        if fs::exists(&path).expect("something went wrong!") {
-            return;
+        if !is_storable_path(&file_path_from_root) {
+            return Err(ObjectError::UnsupportedPath(file_path_from_root));
         }
 
-        match fs::create_dir(&path) {
-            Ok(dir) => dir,
-            Err(e) => panic!("Error on create prefix dir: {}", e),
-        };
-    }
-
-    fn create_suffix_file(&self, prefix: &str, suffix: &str) -> fs::File {
-        let path = self
-            .root_path
-            .clone()
-            .join(OBJECTS_FOLDER_NAME)
-            .join(prefix)
-            .join(suffix);
-
-        let file_exists = match fs::exists(&path) {
+        let metadata = match fs::metadata(path) {
             Ok(v) => v,
-            Err(_e) => panic!("Could not create suffix file!"),
+            Err(e) => panic!("Could not read metadata of {}! {}", path, e),
         };
 
-        if file_exists {
-            let file = match fs::OpenOptions::new().write(true).open(path) {
-                Ok(v) => v,
-                Err(_e) => panic!("Could not open suffix file!"),
-            };
-
-            return file;
+        if staging_area.is_unchanged(&file_path_from_root, &metadata) {
+            return Ok(());
         }
 
-        let file = match fs::File::create(&path) {
-            Ok(v) => v,
-            Err(e) => panic!("Error on create root dir: {}", e),
-        };
+        let file_hash = write_blob_from_file(&self.root_path, path)?;
+
+        staging_area.add(
+            file_path_from_root,
+            file_hash,
+            FileStat::from_metadata(&metadata),
+        );
 
-        file
+        Ok(())
     }
 }
@@ -0,0 +1,237 @@
+use std::{path, process};
+
+use crate::{
+    commit::Commit,
+    object::{read_object_of_kind, ObjectKind},
+    refs::{
+        branch_ref_name, current_branch, delete_ref, is_valid_branch_name, list_branches, read_ref,
+        ref_name_conflicts, rename_ref, resolve_head, resolve_revision, set_head_to_branch,
+        write_ref,
+    },
+    traits::ExecutableCommand,
+};
+
+pub struct BranchCommand {
+    root_path: path::PathBuf,
+    action: BranchAction,
+}
+
+enum BranchAction {
+    List,
+    Create {
+        name: String,
+        start: Option<String>,
+    },
+    Delete {
+        name: String,
+        force: bool,
+    },
+    Rename {
+        old_name: Option<String>,
+        new_name: String,
+    },
+}
+
+impl ExecutableCommand for BranchCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        let usage_msg = "Usage:".to_owned()
+            + "\n\n"
+            + "kvc branch -> list the branches"
+            + "\n"
+            + "kvc branch <name> [<start>] -> create a branch at HEAD or at <start>"
+            + "\n"
+            + "kvc branch (-d|-D) <name> -> delete a branch (-D skips the merged check)"
+            + "\n"
+            + "kvc branch -m [<old>] <new> -> rename a branch";
+
+        let first = args.first().map(|v| &v[..]);
+
+        let action = match (first, args.len()) {
+            (None, _) => BranchAction::List,
+            (Some("-d") | Some("-D"), 2) => BranchAction::Delete {
+                name: args[1].clone(),
+                force: first == Some("-D"),
+            },
+            (Some("-m"), 2) => BranchAction::Rename {
+                old_name: None,
+                new_name: args[1].clone(),
+            },
+            (Some("-m"), 3) => BranchAction::Rename {
+                old_name: Some(args[1].clone()),
+                new_name: args[2].clone(),
+            },
+            (Some(name), 1 | 2) if !name.starts_with('-') => BranchAction::Create {
+                name: name.to_owned(),
+                start: args.get(1).cloned(),
+            },
+            _ => {
+                println!("{}", usage_msg);
+                process::exit(1);
+            }
+        };
+
+        BranchCommand {
+            root_path: root_folder,
+            action,
+        }
+    }
+
+    fn run(&self) {
+        match &self.action {
+            BranchAction::List => self.list(),
+            BranchAction::Create { name, start } => self.create(name, start),
+            BranchAction::Delete { name, force } => self.delete(name, *force),
+            BranchAction::Rename { old_name, new_name } => self.rename(old_name, new_name),
+        }
+    }
+}
+
+impl BranchCommand {
+    fn list(&self) {
+        let current = current_branch(&self.root_path);
+
+        if current.is_none() {
+            let head = resolve_head(&self.root_path).unwrap_or_default();
+            println!("* (HEAD detached at {})", &head[..head.len().min(7)]);
+        }
+
+        for (name, _hash) in list_branches(&self.root_path) {
+            if Some(&name) == current.as_ref() {
+                println!("* \x1b[32m{}\x1b[0m", name);
+                continue;
+            }
+
+            println!("  {}", name);
+        }
+    }
+
+    fn create(&self, name: &str, start: &Option<String>) {
+        self.validate_new_name(name);
+
+        let start_name = start.clone().unwrap_or("HEAD".to_owned());
+        let start_hash = match resolve_revision(&self.root_path, &start_name) {
+            Ok(Some(v)) => v,
+            Err(e) => {
+                println!("Could not resolve {}: {}", start_name, e);
+                process::exit(1);
+            }
+            Ok(None) => {
+                println!("Not a valid commit to start the branch: {}", start_name);
+                process::exit(1);
+            }
+        };
+
+        // A branch must point to a commit, not to a tree or a blob named by
+        // its hash.
+        if let Err(e) = read_object_of_kind(&self.root_path, &start_hash, ObjectKind::Commit) {
+            println!(
+                "Not a valid commit to start the branch: {} ({})",
+                start_name, e
+            );
+            process::exit(1);
+        }
+
+        write_ref(
+            &self.root_path,
+            &branch_ref_name(name),
+            &start_hash,
+            &format!("branch: Created from {}", start_name),
+        );
+
+        println!("Branch {} created at {}", name, &start_hash[..7]);
+    }
+
+    fn delete(&self, name: &str, force: bool) {
+        let branch_hash = match read_ref(&self.root_path, &branch_ref_name(name)) {
+            Some(v) => v,
+            None => {
+                println!("The branch {} does not exist!", name);
+                process::exit(1);
+            }
+        };
+
+        if current_branch(&self.root_path).as_deref() == Some(name) {
+            println!(
+                "You cannot delete the branch {} while it is checked out!",
+                name
+            );
+            process::exit(1);
+        }
+
+        let is_merged = match resolve_head(&self.root_path) {
+            Some(head) => Commit::is_ancestor(&self.root_path, &branch_hash, &head),
+            None => false,
+        };
+
+        if !force && !is_merged {
+            let unmerged_msg = format!("The branch {} is not fully merged.", name)
+                + "\n"
+                + &format!(
+                    "If you are sure you want to delete it, run `kvc branch -D {}`.",
+                    name
+                );
+
+            println!("{}", unmerged_msg);
+            process::exit(1);
+        }
+
+        delete_ref(&self.root_path, &branch_ref_name(name));
+
+        println!("Deleted branch {} (was {})", name, &branch_hash[..7]);
+    }
+
+    fn rename(&self, old_name: &Option<String>, new_name: &str) {
+        let current = current_branch(&self.root_path);
+
+        let old_name = match old_name.clone().or(current.clone()) {
+            Some(v) => v,
+            None => {
+                println!("You must pass the branch to rename while HEAD is detached!");
+                process::exit(1);
+            }
+        };
+
+        self.validate_new_name(new_name);
+
+        let is_current = current.as_deref() == Some(&old_name[..]);
+
+        if read_ref(&self.root_path, &branch_ref_name(&old_name)).is_none() {
+            if is_current {
+                // The branch has no commits yet, so only HEAD needs to move.
+                set_head_to_branch(&self.root_path, new_name);
+                println!("Branch {} renamed to {}", old_name, new_name);
+                return;
+            }
+
+            println!("The branch {} does not exist!", old_name);
+            process::exit(1);
+        }
+
+        rename_ref(
+            &self.root_path,
+            &branch_ref_name(&old_name),
+            &branch_ref_name(new_name),
+        );
+
+        if is_current {
+            set_head_to_branch(&self.root_path, new_name);
+        }
+
+        println!("Branch {} renamed to {}", old_name, new_name);
+    }
+
+    fn validate_new_name(&self, name: &str) {
+        if !is_valid_branch_name(name) {
+            println!("{} is not a valid branch name!", name);
+            process::exit(1);
+        }
+
+        if ref_name_conflicts(&self.root_path, &branch_ref_name(name)) {
+            println!(
+                "A branch named {} already exists or conflicts with another branch!",
+                name
+            );
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    io::{self, Write},
+    path, process,
+};
+
+use crate::{object::read_object, refs::resolve_object, traits::ExecutableCommand};
+
+pub struct CatFileCommand {
+    root_path: path::PathBuf,
+    mode: CatFileMode,
+    revision: String,
+}
+
+enum CatFileMode {
+    Kind,
+    Size,
+    Pretty,
+}
+
+impl ExecutableCommand for CatFileCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        let usage_msg = "Usage: kvc cat-file (-t|-s|-p) <hash>";
+
+        let mode = match args.first().map(|v| &v[..]) {
+            Some("-t") => CatFileMode::Kind,
+            Some("-s") => CatFileMode::Size,
+            Some("-p") => CatFileMode::Pretty,
+            _ => {
+                println!("{}", usage_msg);
+                process::exit(1);
+            }
+        };
+
+        let revision = match args.get(1) {
+            Some(v) if args.len() == 2 => v,
+            _ => {
+                println!("{}", usage_msg);
+                process::exit(1);
+            }
+        };
+
+        CatFileCommand {
+            root_path: root_folder,
+            mode,
+            revision: revision.to_owned(),
+        }
+    }
+
+    fn run(&self) {
+        let hash = match resolve_object(&self.root_path, &self.revision) {
+            Ok(Some(v)) => v,
+            Err(e) => {
+                println!("Could not resolve {}: {}", self.revision, e);
+                process::exit(1);
+            }
+            Ok(None) => {
+                println!("Not a valid object name: {}", self.revision);
+                process::exit(1);
+            }
+        };
+
+        let object = match read_object(&self.root_path, &hash) {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Could not read object {}: {}", hash, e);
+                process::exit(1);
+            }
+        };
+
+        match self.mode {
+            CatFileMode::Kind => println!("{}", object.kind),
+            CatFileMode::Size => println!("{}", object.content.len()),
+            CatFileMode::Pretty => {
+                let mut stdout = io::stdout();
+
+                match stdout.write_all(&object.content) {
+                    Ok(v) => v,
+                    Err(e) => panic!("Could not write object content! {}", e),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::{path, process};
+
+use crate::{
+    ignore::IgnoreRules, traits::ExecutableCommand, utils::get_file_path_relative_to_root,
+};
+
+pub struct CheckIgnoreCommand {
+    root_path: path::PathBuf,
+    paths: Vec<String>,
+    verbose: bool,
+}
+
+impl ExecutableCommand for CheckIgnoreCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        let verbose = args.iter().any(|v| v == "-v" || v == "--verbose");
+        let paths: Vec<String> = args.into_iter().filter(|v| !v.starts_with('-')).collect();
+
+        if paths.is_empty() {
+            println!("Usage: kvc check-ignore [-v] <path>...");
+            process::exit(1);
+        }
+
+        CheckIgnoreCommand {
+            root_path: root_folder,
+            paths,
+            verbose,
+        }
+    }
+
+    fn run(&self) {
+        let ignore_rules = IgnoreRules::load(&self.root_path);
+        let mut any_ignored = false;
+
+        for path in &self.paths {
+            let relative_path =
+                get_file_path_relative_to_root(self.root_path.clone(), path.to_owned());
+            let is_dir = self.root_path.join(&relative_path).is_dir();
+
+            let rule = match ignore_rules.matching_rule(&relative_path, is_dir) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            // With -v the re-including rule is shown too, like in gitignore.
+            if rule.is_negated() && !self.verbose {
+                continue;
+            }
+
+            if !rule.is_negated() {
+                any_ignored = true;
+            }
+
+            match self.verbose {
+                true => println!("{}:{}:{}\t{}", rule.source, rule.line, rule.pattern, path),
+                false => println!("{}", path),
+            }
+        }
+
+        if !any_ignored {
+            process::exit(1);
+        }
+    }
+}
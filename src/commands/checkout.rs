@@ -0,0 +1,68 @@
+use std::{path, process};
+
+use crate::{
+    commands::switch::SwitchCommand,
+    refs::{branch_ref_name, read_ref},
+    traits::ExecutableCommand,
+};
+
+pub struct CheckoutCommand {
+    switch: SwitchCommand,
+}
+
+impl ExecutableCommand for CheckoutCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        let usage_msg = "Usage:".to_owned()
+            + "\n\n"
+            + "kvc checkout <branch> -> switch to an existing branch"
+            + "\n"
+            + "kvc checkout <commit> -> detach HEAD at a commit"
+            + "\n"
+            + "kvc checkout -b <new-branch> [<start>] -> create a branch and switch to it"
+            + "\n\n"
+            + "Pass -f or --force to discard local changes that would be overwritten.";
+
+        let mut target = None;
+        let mut new_branch = None;
+        let mut detach = false;
+        let mut force = false;
+
+        let mut args_iter = args.into_iter();
+
+        while let Some(arg) = args_iter.next() {
+            match &arg[..] {
+                "-b" => new_branch = args_iter.next(),
+                "--detach" => detach = true,
+                "-f" | "--force" => force = true,
+                _ if arg.starts_with('-') || target.is_some() => {
+                    println!("{}", usage_msg);
+                    process::exit(1);
+                }
+                _ => target = Some(arg),
+            }
+        }
+
+        let target_name = match (&target, &new_branch) {
+            (Some(v), _) => v.clone(),
+            (None, Some(_)) => String::new(),
+            (None, None) => {
+                println!("{}", usage_msg);
+                process::exit(1);
+            }
+        };
+
+        // Unlike switch, checkout detaches HEAD whenever the target is not a branch.
+        if new_branch.is_none() && read_ref(&root_folder, &branch_ref_name(&target_name)).is_none()
+        {
+            detach = true;
+        }
+
+        CheckoutCommand {
+            switch: SwitchCommand::with_options(root_folder, target, new_branch, detach, force),
+        }
+    }
+
+    fn run(&self) {
+        self.switch.run();
+    }
+}
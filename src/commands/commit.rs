@@ -0,0 +1,188 @@
+use std::{env, fs, path, process};
+
+use crate::{
+    commit::{Commit, Signature},
+    config::Config,
+    constants::{COMMIT_MESSAGE_FILE_PATH, MERGE_HEAD_FILE_PATH, MERGE_MESSAGE_FILE_PATH},
+    object::{write_object, ObjectKind},
+    refs::{current_branch, read_merge_head, resolve_head, update_head},
+    staging_area::StagingArea,
+    traits::ExecutableCommand,
+    tree::write_tree_from_index,
+};
+
+const DEFAULT_EDITOR: &str = "vi";
+
+pub struct CommitCommand {
+    root_path: path::PathBuf,
+    message: Option<String>,
+}
+
+impl ExecutableCommand for CommitCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        let mut message = None;
+        let mut args_iter = args.iter();
+
+        while let Some(arg) = args_iter.next() {
+            match &arg[..] {
+                "-m" | "--message" => match args_iter.next() {
+                    Some(v) => message = Some(v.to_owned()),
+                    None => {
+                        println!("You must pass a message after {}", arg);
+                        process::exit(1);
+                    }
+                },
+                _ => {
+                    println!("Unknown commit argument: {}", arg);
+                    process::exit(1);
+                }
+            }
+        }
+
+        CommitCommand {
+            root_path: root_folder,
+            message,
+        }
+    }
+
+    fn run(&self) {
+        let config = read_committer_config(&self.root_path);
+
+        let staging_area = StagingArea::open(self.root_path.clone());
+        if !staging_area.conflicts().is_empty() {
+            println!(
+                "You have unmerged paths. Fix the conflicts and `kvc add` them before committing."
+            );
+            process::exit(1);
+        }
+
+        if staging_area.entries().is_empty() {
+            println!("Nothing to commit, the index is empty!");
+            process::exit(1);
+        }
+
+        let parent = resolve_head(&self.root_path);
+        let merge_head = read_merge_head(&self.root_path);
+        let tree = write_tree_from_index(&self.root_path, staging_area.entries());
+
+        if let Some(parent_hash) = &parent {
+            if merge_head.is_none() && Commit::read(&self.root_path, parent_hash).tree == tree {
+                println!("Nothing to commit, the index matches the last commit!");
+                process::exit(1);
+            }
+        }
+
+        let message = self.get_message();
+        let reflog_prefix = match merge_head {
+            Some(_) => "commit (merge)",
+            None => "commit",
+        };
+
+        let commit = Commit {
+            tree,
+            parents: parent.into_iter().chain(merge_head).collect(),
+            author: Signature::now(&config.user),
+            committer: Signature::now(&config.user),
+            message,
+        };
+
+        let commit_hash = write_object(
+            &self.root_path,
+            ObjectKind::Commit,
+            commit.to_string().as_bytes(),
+        );
+
+        let summary = commit.summary();
+        update_head(
+            &self.root_path,
+            &commit_hash,
+            &format!("{}: {}", reflog_prefix, summary),
+        );
+
+        fs::remove_file(self.root_path.join(MERGE_HEAD_FILE_PATH)).unwrap_or_default();
+        fs::remove_file(self.root_path.join(MERGE_MESSAGE_FILE_PATH)).unwrap_or_default();
+
+        let branch_name = match current_branch(&self.root_path) {
+            Some(v) => v,
+            None => "detached HEAD".to_owned(),
+        };
+
+        println!("[{} {}] {}", branch_name, &commit_hash[..7], summary);
+    }
+}
+
+impl CommitCommand {
+    fn get_message(&self) -> String {
+        // Only the editor template has comment lines, a message given with
+        // -m is kept as written.
+        let message = match &self.message {
+            Some(v) => v.trim().to_owned(),
+            None => self
+                .read_message_from_editor()
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .collect::<Vec<&str>>()
+                .join("\n")
+                .trim()
+                .to_owned(),
+        };
+
+        if message.is_empty() {
+            println!("Aborting commit due to empty commit message.");
+            process::exit(1);
+        }
+
+        format!("{}\n", message)
+    }
+
+    fn read_message_from_editor(&self) -> String {
+        let message_path = self.root_path.join(COMMIT_MESSAGE_FILE_PATH);
+        let merge_message =
+            fs::read_to_string(self.root_path.join(MERGE_MESSAGE_FILE_PATH)).unwrap_or_default();
+        let template = merge_message
+            + "\n# Please enter the commit message for your changes."
+            + " Lines starting\n# with '#' will be ignored,"
+            + " and an empty message aborts the commit.\n";
+
+        match fs::write(&message_path, template) {
+            Ok(v) => v,
+            Err(e) => panic!("Could not create commit message file! {}", e),
+        }
+
+        let editor = env::var("EDITOR").unwrap_or(DEFAULT_EDITOR.to_owned());
+        let status = match process::Command::new(&editor).arg(&message_path).status() {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Could not open the editor {}: {}", editor, e);
+                process::exit(1);
+            }
+        };
+
+        if !status.success() {
+            println!("The editor exited with an error, aborting commit.");
+            process::exit(1);
+        }
+
+        match fs::read_to_string(&message_path) {
+            Ok(v) => v,
+            Err(e) => panic!("Could not read commit message file! {}", e),
+        }
+    }
+}
+
+pub(super) fn read_committer_config(root_path: &path::Path) -> Config {
+    let config = Config::load(root_path);
+
+    if config.user.name.is_empty() || config.user.email.is_empty() {
+        let missing_user_msg = "You must set who you are before committing.".to_owned()
+            + "\n\n"
+            + "kvc config [--global] user.name {your name}"
+            + "\n"
+            + "kvc config [--global] user.email {your email}";
+
+        println!("{}", missing_user_msg);
+        process::exit(1);
+    }
+
+    config
+}
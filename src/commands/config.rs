@@ -1,16 +1,25 @@
 use std::{fmt, path};
 
-use crate::{commands::ExecutableCommand, config, traits::FromString};
+use crate::{
+    commands::ExecutableCommand,
+    config::{Config, ConfigEntry, ConfigScope},
+    traits::FromString,
+};
 
 pub struct ConfigCommand {
+    root_path: path::PathBuf,
     config_name: ConfigName,
     config_value: String,
+    scope: Option<ConfigScope>,
+    show_origin: bool,
 }
 
 #[derive(PartialEq)]
 enum ConfigName {
     List,
     BaseBranch,
+    DiffContext,
+    CoreThreads,
     UserName,
     UserEmail,
     Invalid,
@@ -20,6 +29,8 @@ impl FromString for ConfigName {
     fn from_string(s: String) -> Self {
         match &s[..] {
             "base_branch" => ConfigName::BaseBranch,
+            "diff.context" => ConfigName::DiffContext,
+            "core.threads" => ConfigName::CoreThreads,
             "user.name" => ConfigName::UserName,
             "user.email" => ConfigName::UserEmail,
             "" => ConfigName::List,
@@ -33,6 +44,8 @@ impl fmt::Display for ConfigName {
         match *self {
             ConfigName::List => write!(f, "list"),
             ConfigName::BaseBranch => write!(f, "base_branch"),
+            ConfigName::DiffContext => write!(f, "diff.context"),
+            ConfigName::CoreThreads => write!(f, "core.threads"),
             ConfigName::UserName => write!(f, "user.name"),
             ConfigName::UserEmail => write!(f, "user.email"),
             ConfigName::Invalid => write!(f, "invalid"),
@@ -40,89 +53,129 @@ impl fmt::Display for ConfigName {
     }
 }
 
-const POSSIBLE_CONFIG_NAMES: [ConfigName; 4] = [
+const POSSIBLE_CONFIG_NAMES: [ConfigName; 6] = [
     ConfigName::List,
     ConfigName::BaseBranch,
+    ConfigName::DiffContext,
+    ConfigName::CoreThreads,
     ConfigName::UserName,
     ConfigName::UserEmail,
 ];
 
 impl ExecutableCommand for ConfigCommand {
-    fn new(args: Vec<String>, _root_folder: path::PathBuf) -> Self {
-        let config_name = match args.first() {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        let mut scope = None;
+        let mut show_origin = false;
+        let mut positional: Vec<&String> = Vec::new();
+
+        for arg in &args {
+            match &arg[..] {
+                "--global" | "--local" if scope.is_some() => {
+                    println!("Only one of --global and --local can be used!");
+                    std::process::exit(1);
+                }
+                "--global" => scope = Some(ConfigScope::Global),
+                "--local" => scope = Some(ConfigScope::Local),
+                "--show-origin" => show_origin = true,
+                _ => positional.push(arg),
+            }
+        }
+
+        let config_name = match positional.first() {
             Some(value) => value,
             None => &String::new(),
         };
 
-        let config_value = match args.get(1) {
+        let config_value = match positional.get(1) {
             Some(value) => value,
             None => &String::from(""),
         };
 
         ConfigCommand {
-            config_name: ConfigName::from_string(config_name.to_owned()),
-            config_value: config_value.to_owned(),
+            root_path: root_folder,
+            config_name: ConfigName::from_string(config_name.to_string()),
+            config_value: config_value.to_string(),
+            scope,
+            show_origin,
         }
     }
 
     fn run(&self) {
-        if self.config_name.to_string() != ""
-            && !POSSIBLE_CONFIG_NAMES.iter().any(|v| *v == self.config_name)
+        if self.config_name.to_string() != "" && !POSSIBLE_CONFIG_NAMES.contains(&self.config_name)
         {
             println!("This config does not exists!");
             std::process::exit(1);
         }
 
-        let config = match config::Config::read_from_file() {
-            Ok(config) => config,
-            Err(e) => {
-                println!("Error reading the config file: {}", e);
-                std::process::exit(1);
-            }
-        };
+        let is_writing = self.config_name != ConfigName::List && self.config_value != *"";
+        let needs_repo =
+            self.scope == Some(ConfigScope::Local) || (is_writing && self.scope.is_none());
+
+        if needs_repo && self.root_path.as_os_str().is_empty() {
+            let not_kvc_repo_msg = "This is not a KVC repository!".to_owned()
+                + "\n\n"
+                + "Use `kvc config --global` to change your user config instead.";
+
+            println!("{}", not_kvc_repo_msg);
+            std::process::exit(1);
+        }
 
         if self.config_name == ConfigName::List {
-            println!("{}", config);
+            self.list();
             return;
         }
 
-        self.print_or_update(config);
+        self.print_or_update();
     }
 }
 
 impl ConfigCommand {
-    fn print_or_update(&self, config: config::Config) {
-        if self.config_value == *"" {
-            match self.config_name {
-                ConfigName::BaseBranch => println!("{}", config.base_branch),
-                ConfigName::UserName => println!("{}", config.user.name),
-                ConfigName::UserEmail => println!("{}", config.user.email),
-                _ => (),
-            }
-
+    /// Without flags, the values in effect. With a scope, what its file sets,
+    /// and with `--show-origin`, every value of every layer and its source.
+    fn list(&self) {
+        if self.scope.is_none() && !self.show_origin {
+            println!("{}", Config::load(&self.root_path));
             return;
         }
 
-        let mut new_config = config.clone();
-
-        match self.config_name {
-            ConfigName::BaseBranch => new_config.base_branch = self.config_value.clone(),
-            ConfigName::UserName => new_config.user.name = self.config_value.clone(),
-            ConfigName::UserEmail => new_config.user.email = self.config_value.clone(),
-            _ => (),
+        for entry in self.scoped_entries() {
+            match self.show_origin {
+                true => println!("{}\t{}={}", entry.origin, entry.key, entry.value),
+                false => println!("{}={}", entry.key, entry.value),
+            }
         }
+    }
 
-        match config::Config::write_to_file(new_config) {
-            Ok(_) => (),
-            Err(e) => {
-                println!("Something went wrong saving config: {}", e);
-                std::process::exit(1);
+    fn print_or_update(&self) {
+        let key = self.config_name.to_string();
+
+        if self.config_value == *"" {
+            // Later layers override earlier ones, so the last entry wins.
+            let entries = self.scoped_entries();
+            let entry = entries.iter().rev().find(|v| v.key == key);
+
+            match entry {
+                Some(v) if self.show_origin => println!("{}\t{}", v.origin, v.value),
+                Some(v) => println!("{}", v.value),
+                None => println!(),
             }
+
+            return;
         }
 
+        let scope = self.scope.unwrap_or(ConfigScope::Local);
+        Config::write_value(scope, &self.root_path, &key, &self.config_value);
+
         println!(
             "Config {} was updated successfully with {}",
             self.config_name, self.config_value
         );
     }
+
+    fn scoped_entries(&self) -> Vec<ConfigEntry> {
+        match self.scope {
+            Some(scope) => Config::read_scope(scope, &self.root_path),
+            None => Config::entries(&self.root_path),
+        }
+    }
 }
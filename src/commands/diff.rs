@@ -0,0 +1,486 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    io::{self, IsTerminal, Write},
+    path, process,
+};
+
+use crate::{
+    commit::Commit,
+    config::Config,
+    diff::{build_hunks, count_changes, is_binary, myers_diff, split_lines, Edit},
+    object::{hash_file, read_object_of_kind, ObjectKind},
+    refs::{resolve_head, resolve_revision},
+    staging_area::StagingArea,
+    traits::ExecutableCommand,
+    tree::flatten_tree,
+    utils::get_file_path_relative_to_root,
+};
+
+const DEFAULT_CONTEXT_LINES: usize = 3;
+const MAX_STAT_BAR_WIDTH: usize = 40;
+const NULL_FILE_PATH: &str = "/dev/null";
+
+pub struct DiffCommand {
+    root_path: path::PathBuf,
+    staged: bool,
+    revisions: Vec<String>,
+    paths: Vec<String>,
+    format: DiffFormat,
+    context: Option<usize>,
+    color: bool,
+}
+
+#[derive(PartialEq)]
+enum DiffFormat {
+    Patch,
+    Stat,
+    NameOnly,
+}
+
+/// One side of the comparison: paths mapped to blob hashes, read from the
+/// working tree instead of the object store when `on_disk` is set.
+struct Snapshot {
+    files: HashMap<String, String>,
+    on_disk: bool,
+}
+
+struct FileDiff {
+    file_path: String,
+    old_hash: Option<String>,
+    new_hash: Option<String>,
+    old_content: Vec<u8>,
+    new_content: Vec<u8>,
+}
+
+impl ExecutableCommand for DiffCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        let mut command = DiffCommand {
+            root_path: root_folder.clone(),
+            staged: false,
+            revisions: Vec::new(),
+            paths: Vec::new(),
+            format: DiffFormat::Patch,
+            context: None,
+            color: io::stdout().is_terminal(),
+        };
+
+        let mut args_iter = args.into_iter();
+        let mut only_paths = false;
+
+        while let Some(arg) = args_iter.next() {
+            if only_paths {
+                command
+                    .paths
+                    .push(get_file_path_relative_to_root(root_folder.clone(), arg));
+                continue;
+            }
+
+            match &arg[..] {
+                "--" => only_paths = true,
+                "--staged" | "--cached" => command.staged = true,
+                "--stat" => command.format = DiffFormat::Stat,
+                "--name-only" => command.format = DiffFormat::NameOnly,
+                "--color" => command.color = true,
+                "--no-color" => command.color = false,
+                "-U" | "--unified" => {
+                    command.context = Some(Self::parse_context(args_iter.next()));
+                }
+                _ if arg.starts_with("--unified=") => {
+                    command.context = Some(Self::parse_context(Some(arg[10..].to_owned())));
+                }
+                _ if arg.starts_with("-U") => {
+                    command.context = Some(Self::parse_context(Some(arg[2..].to_owned())));
+                }
+                _ if arg.starts_with('-') => {
+                    println!("Unknown diff argument: {}", arg);
+                    process::exit(1);
+                }
+                _ if Self::is_range(&root_folder, &arg) => {
+                    let (from, to) = arg.split_once("..").unwrap_or_default();
+                    command.revisions.push(Self::or_head(from));
+                    command.revisions.push(Self::or_head(to));
+                }
+                _ if command.revisions.len() < 2
+                    && !matches!(resolve_revision(&root_folder, &arg), Ok(None)) =>
+                {
+                    command.revisions.push(arg);
+                }
+                _ => command
+                    .paths
+                    .push(get_file_path_relative_to_root(root_folder.clone(), arg)),
+            }
+        }
+
+        if command.revisions.len() > 2 || (command.staged && command.revisions.len() > 1) {
+            println!("Too many revisions to compare!");
+            process::exit(1);
+        }
+
+        command
+    }
+
+    fn run(&self) {
+        let (old, new) = match (self.revisions.len(), self.staged) {
+            (0, false) => {
+                let index = self.index_snapshot();
+                let worktree = self.worktree_snapshot(index.files.keys());
+                (index, worktree)
+            }
+            (0, true) => (self.revision_snapshot("HEAD"), self.index_snapshot()),
+            (1, false) => {
+                let commit = self.revision_snapshot(&self.revisions[0]);
+                let index = self.index_snapshot();
+                let worktree =
+                    self.worktree_snapshot(commit.files.keys().chain(index.files.keys()));
+                (commit, worktree)
+            }
+            (1, true) => (
+                self.revision_snapshot(&self.revisions[0]),
+                self.index_snapshot(),
+            ),
+            _ => (
+                self.revision_snapshot(&self.revisions[0]),
+                self.revision_snapshot(&self.revisions[1]),
+            ),
+        };
+
+        let changed: BTreeSet<&String> = old
+            .files
+            .keys()
+            .chain(new.files.keys())
+            .filter(|v| old.files.get(*v) != new.files.get(*v))
+            .filter(|v| self.matches_paths(v))
+            .collect();
+
+        let file_diffs: Vec<FileDiff> = changed
+            .into_iter()
+            .map(|file_path| FileDiff {
+                file_path: file_path.clone(),
+                old_hash: old.files.get(file_path).cloned(),
+                new_hash: new.files.get(file_path).cloned(),
+                old_content: self.read_content(&old, file_path),
+                new_content: self.read_content(&new, file_path),
+            })
+            .collect();
+
+        let output = match self.format {
+            DiffFormat::NameOnly => file_diffs
+                .iter()
+                .map(|v| format!("{}\n", v.file_path).into_bytes())
+                .collect::<Vec<Vec<u8>>>()
+                .concat(),
+            DiffFormat::Stat => self.format_stat(&file_diffs),
+            DiffFormat::Patch => self.format_patch(&file_diffs),
+        };
+
+        // A closed pipe (e.g. `kvc diff | head`) is not an error worth reporting.
+        io::stdout().write_all(&output).unwrap_or_default();
+    }
+}
+
+impl DiffCommand {
+    fn parse_context(value: Option<String>) -> usize {
+        match value.and_then(|v| v.parse::<usize>().ok()) {
+            Some(v) => v,
+            None => {
+                println!("The number of context lines must be a positive number");
+                process::exit(1);
+            }
+        }
+    }
+
+    /// Whether `arg` is a `<from>..<to>` range whose sides both resolve, so
+    /// paths like `../file` or `a..b.txt` are still taken as pathspecs.
+    fn is_range(root_folder: &path::Path, arg: &str) -> bool {
+        match arg.split_once("..") {
+            Some((from, to)) => [from, to]
+                .iter()
+                .all(|v| !matches!(resolve_revision(root_folder, &Self::or_head(v)), Ok(None))),
+            None => false,
+        }
+    }
+
+    fn or_head(revision: &str) -> String {
+        match revision.is_empty() {
+            true => "HEAD".to_owned(),
+            false => revision.to_owned(),
+        }
+    }
+
+    fn context_lines(&self) -> usize {
+        if let Some(context) = self.context {
+            return context;
+        }
+
+        Config::load(&self.root_path)
+            .diff_context
+            .parse::<usize>()
+            .unwrap_or(DEFAULT_CONTEXT_LINES)
+    }
+
+    fn matches_paths(&self, file_path: &str) -> bool {
+        self.paths.is_empty()
+            || self.paths.iter().any(|v| {
+                let prefix = v.trim_end_matches('/');
+                prefix.is_empty()
+                    || file_path == prefix
+                    || file_path.starts_with(&format!("{}/", prefix))
+            })
+    }
+
+    fn index_snapshot(&self) -> Snapshot {
+        let staging_area = StagingArea::open(self.root_path.clone());
+
+        Snapshot {
+            files: staging_area.entries().clone(),
+            on_disk: false,
+        }
+    }
+
+    fn revision_snapshot(&self, revision: &str) -> Snapshot {
+        let files = match resolve_revision(&self.root_path, revision) {
+            Ok(Some(hash)) => {
+                flatten_tree(&self.root_path, &Commit::read(&self.root_path, &hash).tree)
+            }
+            Ok(None) if revision == "HEAD" && resolve_head(&self.root_path).is_none() => {
+                HashMap::new()
+            }
+            Err(e) => {
+                println!("Could not resolve {}: {}", revision, e);
+                process::exit(1);
+            }
+            Ok(None) => {
+                println!("Not a valid revision: {}", revision);
+                process::exit(1);
+            }
+        };
+
+        Snapshot {
+            files,
+            on_disk: false,
+        }
+    }
+
+    fn worktree_snapshot<'a>(&self, candidates: impl Iterator<Item = &'a String>) -> Snapshot {
+        let mut files = HashMap::new();
+
+        for file_path in candidates {
+            let full_path = self.root_path.join(file_path);
+
+            if !full_path.is_file() {
+                continue;
+            }
+
+            match hash_file(&full_path.to_string_lossy()) {
+                Ok(v) => files.insert(file_path.clone(), v),
+                Err(e) => {
+                    println!("Could not read the working tree: {}", e);
+                    process::exit(1);
+                }
+            };
+        }
+
+        Snapshot {
+            files,
+            on_disk: true,
+        }
+    }
+
+    fn read_content(&self, snapshot: &Snapshot, file_path: &str) -> Vec<u8> {
+        let hash = match snapshot.files.get(file_path) {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+
+        if snapshot.on_disk {
+            return fs::read(self.root_path.join(file_path)).unwrap_or_default();
+        }
+
+        match read_object_of_kind(&self.root_path, hash, ObjectKind::Blob) {
+            Ok(v) => v.content,
+            Err(e) => panic!("Could not read blob {}: {}", hash, e),
+        }
+    }
+
+    fn paint(&self, color_code: &str, text: &str) -> String {
+        match self.color && !text.is_empty() {
+            true => format!("\x1b[{}m{}\x1b[0m", color_code, text),
+            false => text.to_owned(),
+        }
+    }
+
+    fn format_patch(&self, file_diffs: &[FileDiff]) -> Vec<u8> {
+        let context = self.context_lines();
+        let mut output: Vec<u8> = Vec::new();
+
+        for file_diff in file_diffs {
+            let old_name = match file_diff.old_hash {
+                Some(_) => format!("a/{}", file_diff.file_path),
+                None => NULL_FILE_PATH.to_owned(),
+            };
+            let new_name = match file_diff.new_hash {
+                Some(_) => format!("b/{}", file_diff.file_path),
+                None => NULL_FILE_PATH.to_owned(),
+            };
+
+            let mut header = format!(
+                "diff --kvc a/{} b/{}\n",
+                file_diff.file_path, file_diff.file_path
+            );
+
+            match (&file_diff.old_hash, &file_diff.new_hash) {
+                (None, Some(_)) => header += "new file\n",
+                (Some(_), None) => header += "deleted file\n",
+                _ => (),
+            }
+
+            header += &format!(
+                "index {}..{}\n",
+                Self::short_hash(&file_diff.old_hash),
+                Self::short_hash(&file_diff.new_hash)
+            );
+
+            if is_binary(&file_diff.old_content) || is_binary(&file_diff.new_content) {
+                output.extend(self.paint("1", header.trim_end()).into_bytes());
+                output.extend(
+                    format!("\nBinary files {} and {} differ\n", old_name, new_name).into_bytes(),
+                );
+                continue;
+            }
+
+            header += &format!("--- {}\n+++ {}", old_name, new_name);
+            output.extend(self.paint("1", &header).into_bytes());
+            output.push(b'\n');
+
+            let old_lines = split_lines(&file_diff.old_content);
+            let new_lines = split_lines(&file_diff.new_content);
+            let edits = myers_diff(&old_lines, &new_lines);
+
+            for hunk in build_hunks(&edits, context) {
+                let hunk_header = format!(
+                    "@@ -{} +{} @@",
+                    Self::hunk_range(hunk.old_start, hunk.old_len),
+                    Self::hunk_range(hunk.new_start, hunk.new_len)
+                );
+                output.extend(self.paint("36", &hunk_header).into_bytes());
+                output.push(b'\n');
+
+                for edit in hunk.edits {
+                    let (marker, color_code, line) = match edit {
+                        Edit::Equal(old_idx, _new_idx) => (" ", "", old_lines[old_idx]),
+                        Edit::Delete(old_idx) => ("-", "31", old_lines[old_idx]),
+                        Edit::Insert(new_idx) => ("+", "32", new_lines[new_idx]),
+                    };
+
+                    let text = String::from_utf8_lossy(line);
+                    let text = format!("{}{}", marker, text.trim_end_matches('\n'));
+
+                    match color_code.is_empty() {
+                        true => output.extend(text.into_bytes()),
+                        false => output.extend(self.paint(color_code, &text).into_bytes()),
+                    }
+                    output.push(b'\n');
+
+                    if !line.ends_with(b"\n") {
+                        output.extend(b"\\ No newline at end of file\n");
+                    }
+                }
+            }
+        }
+
+        output
+    }
+
+    fn format_stat(&self, file_diffs: &[FileDiff]) -> Vec<u8> {
+        let stats: Vec<(&str, Option<(usize, usize)>)> = file_diffs
+            .iter()
+            .map(|file_diff| {
+                if is_binary(&file_diff.old_content) || is_binary(&file_diff.new_content) {
+                    return (&file_diff.file_path[..], None);
+                }
+
+                let old_lines = split_lines(&file_diff.old_content);
+                let new_lines = split_lines(&file_diff.new_content);
+                let edits = myers_diff(&old_lines, &new_lines);
+
+                (&file_diff.file_path[..], Some(count_changes(&edits)))
+            })
+            .collect();
+
+        if stats.is_empty() {
+            return Vec::new();
+        }
+
+        let name_width = stats.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        let max_changes = stats
+            .iter()
+            .filter_map(|(_name, counts)| counts.map(|(ins, del)| ins + del))
+            .max()
+            .unwrap_or(0);
+        let count_width = max_changes.to_string().len().max(3);
+
+        let mut output = String::new();
+        let (mut total_insertions, mut total_deletions) = (0, 0);
+
+        for (name, counts) in &stats {
+            let (insertions, deletions) = match counts {
+                Some(v) => *v,
+                None => {
+                    output += &format!(" {:<name_width$} | {:>count_width$}\n", name, "Bin");
+                    continue;
+                }
+            };
+
+            total_insertions += insertions;
+            total_deletions += deletions;
+
+            let (plus_width, minus_width) = match max_changes > MAX_STAT_BAR_WIDTH {
+                true => (
+                    insertions * MAX_STAT_BAR_WIDTH / max_changes,
+                    deletions * MAX_STAT_BAR_WIDTH / max_changes,
+                ),
+                false => (insertions, deletions),
+            };
+
+            output += &format!(
+                " {:<name_width$} | {:>count_width$} {}{}\n",
+                name,
+                insertions + deletions,
+                self.paint("32", &"+".repeat(plus_width)),
+                self.paint("31", &"-".repeat(minus_width)),
+            );
+        }
+
+        let plural = |count: usize, word: &str| match count {
+            1 => format!("{} {}", count, word),
+            _ => format!("{} {}s", count, word),
+        };
+
+        output += &format!(" {} changed", plural(stats.len(), "file"));
+        if total_insertions > 0 {
+            output += &format!(", {}(+)", plural(total_insertions, "insertion"));
+        }
+        if total_deletions > 0 {
+            output += &format!(", {}(-)", plural(total_deletions, "deletion"));
+        }
+        output += "\n";
+
+        output.into_bytes()
+    }
+
+    fn short_hash(hash: &Option<String>) -> String {
+        match hash {
+            Some(v) => v[..7].to_owned(),
+            None => "0".repeat(7),
+        }
+    }
+
+    fn hunk_range(start: usize, len: usize) -> String {
+        match len {
+            0 => format!("{},0", start),
+            1 => format!("{}", start + 1),
+            _ => format!("{},{}", start + 1, len),
+        }
+    }
+}
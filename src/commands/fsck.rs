@@ -0,0 +1,207 @@
+use std::{collections::HashSet, path, process};
+
+use crate::{
+    commit::Commit,
+    object::{hash_object, pack::verify_checksums, read_object, ObjectKind},
+    reachability::all_objects,
+    refs::{list_refs, read_merge_head, read_reflog_hashes, resolve_head},
+    staging_area::StagingArea,
+    tag::Tag,
+    traits::ExecutableCommand,
+    tree::parse_tree,
+};
+
+pub struct FsckCommand {
+    root_path: path::PathBuf,
+    show_dangling: bool,
+}
+
+struct Reference {
+    hash: String,
+    kind: ObjectKind,
+    referrer: String,
+}
+
+impl ExecutableCommand for FsckCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        let mut show_dangling = true;
+
+        for arg in &args {
+            match &arg[..] {
+                "--no-dangling" => show_dangling = false,
+                _ => {
+                    println!("Unknown fsck argument: {}", arg);
+                    process::exit(1);
+                }
+            }
+        }
+
+        FsckCommand {
+            root_path: root_folder,
+            show_dangling,
+        }
+    }
+
+    fn run(&self) {
+        let mut report: Vec<String> = Vec::new();
+        let mut has_errors = false;
+
+        for corrupt in verify_checksums(&self.root_path) {
+            report.push(format!("corrupt {} checksum mismatch", corrupt.display()));
+            has_errors = true;
+        }
+
+        let objects = all_objects(&self.root_path);
+        let existing: HashSet<&String> = objects.iter().collect();
+        let mut references: Vec<Reference> = Vec::new();
+        let mut object_kinds: Vec<(String, ObjectKind)> = Vec::new();
+
+        for hash in &objects {
+            let object = match read_object(&self.root_path, hash) {
+                Ok(v) => v,
+                Err(e) => {
+                    report.push(format!("corrupt {} {}", hash, e));
+                    has_errors = true;
+                    continue;
+                }
+            };
+
+            let actual_hash = hash_object(object.kind, &object.content);
+            if actual_hash != *hash {
+                report.push(format!("hash-mismatch {} {}", hash, actual_hash));
+                has_errors = true;
+            }
+
+            object_kinds.push((hash.clone(), object.kind));
+
+            match object.kind {
+                ObjectKind::Commit => match Commit::parse(&object.text()) {
+                    Some(commit) => {
+                        references.push(Reference {
+                            hash: commit.tree,
+                            kind: ObjectKind::Tree,
+                            referrer: hash.clone(),
+                        });
+
+                        for parent in commit.parents {
+                            references.push(Reference {
+                                hash: parent,
+                                kind: ObjectKind::Commit,
+                                referrer: hash.clone(),
+                            });
+                        }
+                    }
+                    None => {
+                        report.push(format!("corrupt {} malformed commit", hash));
+                        has_errors = true;
+                    }
+                },
+                ObjectKind::Tree => match parse_tree(&object.text()) {
+                    Some(entries) => {
+                        for entry in entries {
+                            references.push(Reference {
+                                hash: entry.hash,
+                                kind: entry.kind,
+                                referrer: format!("{}:{}", hash, entry.name),
+                            });
+                        }
+                    }
+                    None => {
+                        report.push(format!("corrupt {} malformed tree", hash));
+                        has_errors = true;
+                    }
+                },
+                ObjectKind::Tag => match Tag::parse(&object.text()) {
+                    Some(tag) => references.push(Reference {
+                        hash: tag.object,
+                        kind: tag.kind,
+                        referrer: hash.clone(),
+                    }),
+                    None => {
+                        report.push(format!("corrupt {} malformed tag", hash));
+                        has_errors = true;
+                    }
+                },
+                ObjectKind::Blob => (),
+            }
+        }
+
+        let (index_entries, index_problems) = StagingArea::inspect(self.root_path.clone());
+        for problem in index_problems {
+            report.push(format!("malformed-index {}", problem));
+            has_errors = true;
+        }
+
+        let mut roots: Vec<Reference> = Vec::new();
+        for (file_path, hash) in index_entries {
+            roots.push(Reference {
+                hash,
+                kind: ObjectKind::Blob,
+                referrer: format!("index:{}", file_path),
+            });
+        }
+
+        for (ref_name, hash) in list_refs(&self.root_path) {
+            let kind = match object_kinds.iter().find(|(v, _kind)| *v == hash) {
+                Some((_hash, kind)) => *kind,
+                None => ObjectKind::Commit,
+            };
+
+            roots.push(Reference {
+                hash,
+                kind,
+                referrer: ref_name,
+            });
+        }
+
+        if let Some(hash) = resolve_head(&self.root_path) {
+            roots.push(Reference {
+                hash,
+                kind: ObjectKind::Commit,
+                referrer: "HEAD".to_owned(),
+            });
+        }
+
+        if let Some(hash) = read_merge_head(&self.root_path) {
+            roots.push(Reference {
+                hash,
+                kind: ObjectKind::Commit,
+                referrer: "MERGE_HEAD".to_owned(),
+            });
+        }
+
+        for reference in references.iter().chain(roots.iter()) {
+            if !existing.contains(&reference.hash) {
+                report.push(format!(
+                    "missing {} {} {}",
+                    reference.kind, reference.hash, reference.referrer
+                ));
+                has_errors = true;
+            }
+        }
+
+        if self.show_dangling {
+            let referenced: HashSet<&String> = references
+                .iter()
+                .chain(roots.iter())
+                .map(|v| &v.hash)
+                .collect();
+            let reflog_hashes: HashSet<String> =
+                read_reflog_hashes(&self.root_path).into_iter().collect();
+
+            for (hash, kind) in &object_kinds {
+                if !referenced.contains(hash) && !reflog_hashes.contains(hash) {
+                    report.push(format!("dangling {} {}", kind, hash));
+                }
+            }
+        }
+
+        for line in &report {
+            println!("{}", line);
+        }
+
+        if has_errors {
+            process::exit(1);
+        }
+    }
+}
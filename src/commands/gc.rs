@@ -0,0 +1,86 @@
+use std::{fs, path, process};
+
+use crate::{
+    object::{
+        object_path, pack::verify_checksums, read_object, remove_loose_object, repack_objects,
+        unpack_object,
+    },
+    reachability::{all_objects, collect_roots, find_unreachable, mark_reachable, PruneOptions},
+    traits::ExecutableCommand,
+};
+
+pub struct GcCommand {
+    root_path: path::PathBuf,
+    options: PruneOptions,
+}
+
+impl ExecutableCommand for GcCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        GcCommand {
+            root_path: root_folder,
+            options: PruneOptions::from_args(&args),
+        }
+    }
+
+    fn run(&self) {
+        let reachable = mark_reachable(&self.root_path, collect_roots(&self.root_path));
+        let unreachable = find_unreachable(&self.root_path, &reachable, self.options.expire);
+
+        // Only reachable objects are packed. Unreachable ones still in their
+        // grace period stay loose, keeping their own age, so that a later gc
+        // can expire them.
+        let packed: Vec<String> = all_objects(&self.root_path)
+            .into_iter()
+            .filter(|hash| reachable.contains(hash))
+            .collect();
+
+        if self.options.dry_run {
+            for hash in &unreachable.expired {
+                let kind = match read_object(&self.root_path, hash) {
+                    Ok(v) => v.kind.to_string(),
+                    Err(e) => e.to_string(),
+                };
+
+                println!("Would remove {} ({})", hash, kind);
+            }
+
+            println!(
+                "{} unreachable objects would be removed, {} are newer than the grace period",
+                unreachable.expired.len(),
+                unreachable.recent.len()
+            );
+            println!("{} objects would be packed", packed.len());
+            return;
+        }
+
+        // Objects from a damaged pack would be copied into the new one.
+        if let Some(corrupt) = verify_checksums(&self.root_path).first() {
+            println!(
+                "The pack file {} is corrupt, run `kvc fsck` for details.",
+                corrupt.display()
+            );
+            process::exit(1);
+        }
+
+        for hash in &unreachable.recent {
+            if !fs::exists(object_path(&self.root_path, hash)).unwrap_or_default() {
+                unpack_object(&self.root_path, hash);
+            }
+        }
+
+        let stats = repack_objects(&self.root_path, &packed);
+
+        for hash in &unreachable.expired {
+            if fs::exists(object_path(&self.root_path, hash)).unwrap_or_default() {
+                remove_loose_object(&self.root_path, hash);
+            }
+        }
+
+        println!(
+            "Removed {} unreachable objects, packed {} objects ({} stored as deltas)",
+            unreachable.expired.len(),
+            stats.objects,
+            stats.deltas
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use std::{fs, path, process};
+
+use crate::{
+    object::{hash_file, write_blob_from_file},
+    traits::ExecutableCommand,
+};
+
+pub struct HashObjectCommand {
+    root_path: path::PathBuf,
+    path: String,
+    write: bool,
+}
+
+impl ExecutableCommand for HashObjectCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        let mut write = false;
+        let mut path = None;
+
+        for arg in &args {
+            match &arg[..] {
+                "-w" => write = true,
+                _ if path.is_none() => path = Some(arg.to_owned()),
+                _ => {
+                    println!("Unknown hash-object argument: {}", arg);
+                    process::exit(1);
+                }
+            }
+        }
+
+        let path = match path {
+            Some(v) => v,
+            None => {
+                println!("You must pass a file to hash");
+                process::exit(1);
+            }
+        };
+
+        HashObjectCommand {
+            root_path: root_folder,
+            path,
+            write,
+        }
+    }
+
+    fn run(&self) {
+        let is_file = match fs::metadata(&self.path) {
+            Ok(v) => v.is_file(),
+            Err(_e) => false,
+        };
+
+        if !is_file {
+            println!("The provided path is not a file!");
+            process::exit(1);
+        }
+
+        let hash = match self.write {
+            true => write_blob_from_file(&self.root_path, &self.path),
+            false => hash_file(&self.path),
+        };
+
+        let hash = match hash {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Could not hash {}: {}", self.path, e);
+                process::exit(1);
+            }
+        };
+
+        println!("{}", hash);
+    }
+}
@@ -9,7 +9,7 @@ pub struct HelpCommand {
     pub command_name: Command,
 }
 
-const POSSIBLE_COMMAND_NAMES: [Command; 2] = [Command::Init, Command::Config];
+const POSSIBLE_COMMAND_NAMES: [Command; 3] = [Command::Init, Command::Config, Command::Status];
 
 impl ExecutableCommand for HelpCommand {
     fn new(args: Vec<String>, _root_folder: path::PathBuf) -> Self {
@@ -26,7 +26,9 @@ impl ExecutableCommand for HelpCommand {
                         + "\n\n"
                         + "- init"
                         + "\n"
-                        + "- config";
+                        + "- config"
+                        + "\n"
+                        + "- status";
 
                 println!("{}", none_command_msg);
                 std::process::exit(1);
@@ -39,16 +41,15 @@ impl ExecutableCommand for HelpCommand {
     }
 
     fn run(&self) {
-        if !POSSIBLE_COMMAND_NAMES
-            .iter()
-            .any(|v| *v == self.command_name)
-        {
+        if !POSSIBLE_COMMAND_NAMES.contains(&self.command_name) {
             let non_mapped_command_msg = "This is not a mapped command!".to_owned()
                 + " Try one of the following:"
                 + "\n\n"
                 + "- kvc help init"
                 + "\n"
-                + "- kvc help config";
+                + "- kvc help config"
+                + "\n"
+                + "- kvc help status";
 
             println!("{}", non_mapped_command_msg);
             std::process::exit(1);
@@ -57,6 +58,7 @@ impl ExecutableCommand for HelpCommand {
         match self.command_name {
             Command::Init => Self::help_init(),
             Command::Config => Self::help_config(),
+            Command::Status => Self::help_status(),
             _ => (),
         }
     }
@@ -84,6 +86,10 @@ impl HelpCommand {
                 + "\n\n"
                 + "- base_branch"
                 + "\n"
+                + "- diff.context"
+                + "\n"
+                + "- core.threads (threads used to walk and hash files, 0 or empty for one per core)"
+                + "\n"
                 + "- user.name"
                 + "\n"
                 + "- user.email"
@@ -96,8 +102,45 @@ impl HelpCommand {
                 + "\n"
                 + "kvc config {available_config} {value} -> change the value of the passed config"
                 + "\n"
-                + "kvc config list -> print all the configuration file";
+                + "kvc config list -> print all the configuration file"
+                + "\n\n"
+                + "Values are read from /etc/kvcconfig, then ~/.kvcconfig, then the repository's .kvc/config,"
+                + " then KVC_CONFIG_* environment variables (e.g. KVC_CONFIG_USER_NAME) and finally"
+                + " `kvc -c key=value <command>`, each one overriding the ones before it."
+                + "\n\n"
+                + "kvc config --global ... -> read or change ~/.kvcconfig only"
+                + "\n"
+                + "kvc config --local ... -> read or change the repository config only (the default when changing a value)"
+                + "\n"
+                + "kvc config --show-origin ... -> also show where each value comes from";
 
         println!("{}", help_config_msg);
     }
+
+    fn help_status() {
+        let help_status_msg =
+            "The status command shows what is staged (index against HEAD), what is changed but not staged (working tree against index) and what is untracked."
+                .to_owned()
+                + " It can be used in the following ways:"
+                + "\n\n"
+                + "kvc status -> human readable output"
+                + "\n"
+                + "kvc status -s | --short -> one `XY path` line per file, X for the index and Y for the working tree"
+                + "\n"
+                + "kvc status --porcelain[=v1] -> the short format, never colored and stable across versions"
+                + "\n"
+                + "kvc status --porcelain=v2 -> branch headers plus modes and hashes for every entry"
+                + "\n"
+                + "kvc status --json -> a JSON document with the branch, entries, conflicts and untracked files"
+                + "\n\n"
+                + "Passing -z ends every entry with NUL instead of a newline and writes paths unquoted."
+                + "\n\n"
+                + "Exit codes:"
+                + "\n\n"
+                + "0 -> the status was shown, whether the tree is clean or not"
+                + "\n"
+                + "1 -> invalid arguments or the status could not be read";
+
+        println!("{}", help_status_msg);
+    }
 }
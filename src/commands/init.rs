@@ -1,5 +1,5 @@
 use crate::commands::ExecutableCommand;
-use crate::config::{Config, User};
+use crate::config::Config;
 use core::panic;
 use std::env;
 use std::fs;
@@ -24,8 +24,11 @@ impl ExecutableCommand for InitCommand {
         Self::create_root_folder(&cur_dir);
         Self::create_refs_folder(&cur_dir);
         Self::create_objects_folder(&cur_dir);
-        Self::create_head_file(&cur_dir);
         Self::create_config_file(&cur_dir);
+        // The new repository has no config of its own yet, so the branch
+        // comes from the system and global files (or the default).
+        let base_branch = Config::load(path::Path::new("")).base_branch;
+        Self::create_head_file(&cur_dir, &base_branch);
         Self::create_index_file(&cur_dir);
         Self::hide_root_folder(&cur_dir);
 
@@ -84,7 +87,7 @@ impl InitCommand {
         }
     }
 
-    fn create_head_file(cur_dir: &path::PathBuf) {
+    fn create_head_file(cur_dir: &path::PathBuf, base_branch: &str) {
         const HEAD_FILE_NAME: &str = "HEAD";
 
         let mut file_path = path::PathBuf::from(&cur_dir);
@@ -95,7 +98,7 @@ impl InitCommand {
             Err(e) => panic!("Error creating the HEAD file: {}", e),
         };
 
-        let file_content: &str = "ref: refs/master";
+        let file_content: &str = &format!("ref: refs/{}", base_branch);
 
         match file.write_all(file_content.as_bytes()) {
             Ok(ok) => ok,
@@ -103,33 +106,18 @@ impl InitCommand {
         }
     }
 
+    /// Starts with an empty repository config: anything left unset comes from
+    /// the global and system files or the defaults of `Config::load`.
     fn create_config_file(cur_dir: &path::PathBuf) {
         const CONFIG_FILE_NAME: &str = "config";
 
         let mut file_path = path::PathBuf::from(&cur_dir);
         file_path.extend(&[ROOT_FOLDER_NAME, CONFIG_FILE_NAME]);
 
-        let mut file = match fs::File::create(&file_path) {
+        match fs::File::create(&file_path) {
             Ok(file) => file,
             Err(e) => panic!("Error creating the config file: {}", e),
         };
-
-        const DEFAULT_BASE_BRANCH: &str = "master";
-
-        let default_config = Config {
-            base_branch: DEFAULT_BASE_BRANCH.to_string(),
-            user: User {
-                name: "".to_string(),
-                email: "".to_string(),
-            },
-        };
-
-        let file_content: &str = &default_config.to_string();
-
-        match file.write_all(file_content.as_bytes()) {
-            Ok(ok) => ok,
-            Err(e) => panic!("Error writing to config file: {}", e),
-        };
     }
 
     fn create_index_file(cur_dir: &path::PathBuf) {
@@ -0,0 +1,377 @@
+use std::{
+    collections::{BinaryHeap, HashMap},
+    path, process,
+};
+
+use crate::{
+    commit::Commit,
+    date::{format_timestamp, parse_date},
+    refs::{resolve_head, resolve_revision},
+    traits::ExecutableCommand,
+    tree::find_path_hash,
+    utils::get_file_path_relative_to_root,
+};
+
+pub struct LogCommand {
+    root_path: path::PathBuf,
+    revision: Option<String>,
+    max_count: Option<usize>,
+    oneline: bool,
+    graph: bool,
+    author: Option<String>,
+    since: Option<u64>,
+    until: Option<u64>,
+    paths: Vec<String>,
+}
+
+impl ExecutableCommand for LogCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        let mut command = LogCommand {
+            root_path: root_folder.clone(),
+            revision: None,
+            max_count: None,
+            oneline: false,
+            graph: false,
+            author: None,
+            since: None,
+            until: None,
+            paths: Vec::new(),
+        };
+
+        let mut args_iter = args.into_iter();
+        let mut only_paths = false;
+
+        while let Some(arg) = args_iter.next() {
+            if only_paths {
+                command
+                    .paths
+                    .push(get_file_path_relative_to_root(root_folder.clone(), arg));
+                continue;
+            }
+
+            match &arg[..] {
+                "--" => only_paths = true,
+                "--oneline" => command.oneline = true,
+                "--graph" => command.graph = true,
+                "-n" | "--max-count" => {
+                    command.max_count = Some(Self::parse_count(args_iter.next()));
+                }
+                "--author" => command.author = Some(Self::expect_value(&arg, args_iter.next())),
+                "--since" | "--after" => {
+                    command.since = Some(Self::parse_date_arg(&arg, args_iter.next()))
+                }
+                "--until" | "--before" => {
+                    command.until = Some(Self::parse_date_arg(&arg, args_iter.next()))
+                }
+                _ if arg.starts_with('-') && arg[1..].parse::<usize>().is_ok() => {
+                    command.max_count = Some(Self::parse_count(Some(arg[1..].to_owned())));
+                }
+                _ if arg.starts_with('-') => {
+                    println!("Unknown log argument: {}", arg);
+                    process::exit(1);
+                }
+                _ if command.revision.is_none()
+                    && !matches!(resolve_revision(&root_folder, &arg), Ok(None)) =>
+                {
+                    command.revision = Some(arg);
+                }
+                _ => command
+                    .paths
+                    .push(get_file_path_relative_to_root(root_folder.clone(), arg)),
+            }
+        }
+
+        command
+    }
+
+    fn run(&self) {
+        let start = match &self.revision {
+            Some(revision) => match resolve_revision(&self.root_path, revision) {
+                Ok(v) => v,
+                Err(e) => {
+                    println!("Could not resolve {}: {}", revision, e);
+                    process::exit(1);
+                }
+            },
+            None => resolve_head(&self.root_path),
+        };
+
+        let start = match start {
+            Some(v) => v,
+            None => {
+                println!("Your current branch does not have any commits yet!");
+                process::exit(1);
+            }
+        };
+
+        let commits = self.load_history(&start);
+        let ordered = Self::topological_order(&start, &commits);
+
+        let mut columns: Vec<String> = Vec::new();
+        let mut shown = 0;
+
+        for hash in ordered {
+            if self.max_count.is_some_and(|max| shown >= max) {
+                break;
+            }
+
+            let commit = &commits[&hash];
+            let is_shown = self.matches(commit);
+
+            let (collapse, prefix, rest_prefix, connector) = match self.graph {
+                true => Self::advance_graph(&mut columns, &hash, commit),
+                false => (None, String::new(), String::new(), None),
+            };
+
+            if !is_shown {
+                continue;
+            }
+
+            shown += 1;
+
+            if let Some(collapse) = collapse {
+                println!("{}", collapse);
+            }
+
+            self.print_commit(&hash, commit, &prefix, &rest_prefix);
+
+            if let Some(connector) = connector {
+                println!("{}", connector);
+            }
+        }
+    }
+}
+
+impl LogCommand {
+    fn expect_value(arg: &str, value: Option<String>) -> String {
+        match value {
+            Some(v) => v,
+            None => {
+                println!("You must pass a value after {}", arg);
+                process::exit(1);
+            }
+        }
+    }
+
+    fn parse_count(value: Option<String>) -> usize {
+        match value.and_then(|v| v.parse::<usize>().ok()) {
+            Some(v) => v,
+            None => {
+                println!("The number of commits must be a positive number");
+                process::exit(1);
+            }
+        }
+    }
+
+    fn parse_date_arg(arg: &str, value: Option<String>) -> u64 {
+        let value = Self::expect_value(arg, value);
+
+        match parse_date(&value) {
+            Some(v) => v,
+            None => {
+                println!("Could not understand the date {}", value);
+                process::exit(1);
+            }
+        }
+    }
+
+    fn load_history(&self, start: &str) -> HashMap<String, Commit> {
+        let mut commits: HashMap<String, Commit> = HashMap::new();
+        let mut pending = vec![start.to_owned()];
+
+        while let Some(hash) = pending.pop() {
+            if commits.contains_key(&hash) {
+                continue;
+            }
+
+            let commit = Commit::read(&self.root_path, &hash);
+            pending.extend(commit.parents.iter().cloned());
+            commits.insert(hash, commit);
+        }
+
+        commits
+    }
+
+    fn topological_order(start: &str, commits: &HashMap<String, Commit>) -> Vec<String> {
+        let mut children_count: HashMap<&String, usize> = HashMap::new();
+        for commit in commits.values() {
+            for parent in &commit.parents {
+                *children_count.entry(parent).or_default() += 1;
+            }
+        }
+
+        let mut ready: BinaryHeap<(u64, String)> = BinaryHeap::new();
+        ready.push((commits[start].committer.timestamp, start.to_owned()));
+
+        let mut ordered = Vec::new();
+
+        while let Some((_timestamp, hash)) = ready.pop() {
+            for parent in &commits[&hash].parents {
+                let count = children_count.entry(parent).or_default();
+                *count -= 1;
+
+                if *count == 0 {
+                    ready.push((commits[parent].committer.timestamp, parent.clone()));
+                }
+            }
+
+            ordered.push(hash);
+        }
+
+        ordered
+    }
+
+    fn matches(&self, commit: &Commit) -> bool {
+        if let Some(author) = &self.author {
+            let identity = format!("{} <{}>", commit.author.name, commit.author.email);
+
+            if !identity.contains(author) {
+                return false;
+            }
+        }
+
+        let timestamp = commit.committer.timestamp;
+        if self.since.is_some_and(|since| timestamp < since)
+            || self.until.is_some_and(|until| timestamp > until)
+        {
+            return false;
+        }
+
+        if self.paths.is_empty() {
+            return true;
+        }
+
+        // Like git, a merge only counts as touching the paths when it differs
+        // from every parent there; taking one side's version as-is doesn't.
+        let parent_trees: Vec<Option<String>> = match commit.parents.is_empty() {
+            true => vec![None],
+            false => commit
+                .parents
+                .iter()
+                .map(|parent| Some(Commit::read(&self.root_path, parent).tree))
+                .collect(),
+        };
+
+        let current: Vec<Option<String>> = self
+            .paths
+            .iter()
+            .map(|file_path| find_path_hash(&self.root_path, &commit.tree, file_path))
+            .collect();
+
+        parent_trees.iter().all(|parent_tree| {
+            self.paths.iter().zip(&current).any(|(file_path, current)| {
+                let previous = parent_tree
+                    .as_ref()
+                    .and_then(|tree| find_path_hash(&self.root_path, tree, file_path));
+
+                *current != previous
+            })
+        })
+    }
+
+    fn advance_graph(
+        columns: &mut Vec<String>,
+        hash: &str,
+        commit: &Commit,
+    ) -> (Option<String>, String, String, Option<String>) {
+        let positions: Vec<usize> = columns
+            .iter()
+            .enumerate()
+            .filter(|(_idx, v)| *v == hash)
+            .map(|(idx, _v)| idx)
+            .collect();
+
+        let column = match positions.first() {
+            Some(v) => *v,
+            None => {
+                columns.push(hash.to_owned());
+                columns.len() - 1
+            }
+        };
+
+        // Lanes that reached the same commit join before its row, so the
+        // fork is drawn at the commit itself like in git.
+        for idx in positions.iter().skip(1).rev() {
+            columns.remove(*idx);
+        }
+
+        let collapse = match positions.len() > 1 {
+            true => Some(Self::graph_cells(columns.len(), None) + "/"),
+            false => None,
+        };
+
+        let prefix = Self::graph_cells(columns.len(), Some(column)) + " ";
+        let mut connector = None;
+
+        match commit.parents.split_first() {
+            Some((first_parent, other_parents)) => {
+                columns[column] = first_parent.clone();
+
+                let mut insert_at = column + 1;
+                for parent in other_parents {
+                    if !columns.contains(parent) {
+                        columns.insert(insert_at, parent.clone());
+                        insert_at += 1;
+                    }
+                }
+
+                if insert_at > column + 1 {
+                    connector = Some(Self::graph_cells(column + 1, None) + "\\");
+                }
+            }
+            None => {
+                columns.remove(column);
+            }
+        }
+
+        let rest_prefix = Self::graph_cells(columns.len().max(1), None) + " ";
+
+        (collapse, prefix, rest_prefix, connector)
+    }
+
+    fn graph_cells(width: usize, commit_column: Option<usize>) -> String {
+        (0..width)
+            .map(|idx| match Some(idx) == commit_column {
+                true => "*",
+                false => "|",
+            })
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    fn print_commit(&self, hash: &str, commit: &Commit, prefix: &str, rest_prefix: &str) {
+        if self.oneline {
+            println!(
+                "{}\x1b[33m{}\x1b[0m {}",
+                prefix,
+                &hash[..7],
+                commit.summary()
+            );
+            return;
+        }
+
+        println!("{}\x1b[33mcommit {}\x1b[0m", prefix, hash);
+
+        if commit.parents.len() > 1 {
+            let short_parents: Vec<&str> = commit.parents.iter().map(|v| &v[..7]).collect();
+            println!("{}Merge: {}", rest_prefix, short_parents.join(" "));
+        }
+
+        println!(
+            "{}Author: {} <{}>",
+            rest_prefix, commit.author.name, commit.author.email
+        );
+        println!(
+            "{}Date:   {}",
+            rest_prefix,
+            format_timestamp(commit.author.timestamp, &commit.author.timezone)
+        );
+        println!("{}", rest_prefix.trim_end());
+
+        for line in commit.message.lines() {
+            println!("{}    {}", rest_prefix, line);
+        }
+
+        println!("{}", rest_prefix.trim_end());
+    }
+}
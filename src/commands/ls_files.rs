@@ -0,0 +1,45 @@
+use std::{path, process};
+
+use crate::{staging_area::StagingArea, traits::ExecutableCommand};
+
+pub struct LsFilesCommand {
+    root_path: path::PathBuf,
+    show_hashes: bool,
+}
+
+impl ExecutableCommand for LsFilesCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        let mut show_hashes = false;
+
+        for arg in &args {
+            match &arg[..] {
+                "-s" | "--stage" => show_hashes = true,
+                _ => {
+                    println!("Unknown ls-files argument: {}", arg);
+                    process::exit(1);
+                }
+            }
+        }
+
+        LsFilesCommand {
+            root_path: root_folder,
+            show_hashes,
+        }
+    }
+
+    fn run(&self) {
+        let staging_area = StagingArea::open(self.root_path.clone());
+
+        let mut entries: Vec<(&String, &String)> = staging_area.entries().iter().collect();
+        entries.sort();
+
+        for (file_path, hash) in entries {
+            if self.show_hashes {
+                println!("{}\t{}", hash, file_path);
+                continue;
+            }
+
+            println!("{}", file_path);
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs, path, process,
+};
+
+use crate::{
+    commands::{
+        commit::read_committer_config,
+        status::{Status, StatusCommand},
+        switch::{checkout_tree, overwrites_untracked, write_blob_to_file},
+    },
+    commit::{Commit, Signature},
+    constants::{MERGE_HEAD_FILE_PATH, MERGE_MESSAGE_FILE_PATH},
+    diff::is_binary,
+    merge::merge_lines,
+    object::{read_object_of_kind, write_object, ObjectKind},
+    refs::{
+        branch_ref_name, read_merge_head, read_ref, remove_empty_parents, resolve_head,
+        resolve_revision, update_head,
+    },
+    staging_area::{ConflictEntry, StagingArea},
+    traits::ExecutableCommand,
+    tree::{flatten_tree, write_tree_from_index},
+};
+
+const OURS_LABEL: &str = "HEAD";
+
+pub struct MergeCommand {
+    root_path: path::PathBuf,
+    target: Option<String>,
+    message: Option<String>,
+    no_ff: bool,
+    ff_only: bool,
+    abort: bool,
+}
+
+impl ExecutableCommand for MergeCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        let usage_msg = "Usage:".to_owned()
+            + "\n\n"
+            + "kvc merge [--no-ff|--ff-only] [-m <message>] <branch> -> merge a branch into HEAD"
+            + "\n"
+            + "kvc merge --abort -> drop a conflicted merge and restore HEAD";
+
+        let mut command = MergeCommand {
+            root_path: root_folder,
+            target: None,
+            message: None,
+            no_ff: false,
+            ff_only: false,
+            abort: false,
+        };
+
+        let mut args_iter = args.into_iter();
+
+        while let Some(arg) = args_iter.next() {
+            match &arg[..] {
+                "--no-ff" => command.no_ff = true,
+                "--ff-only" => command.ff_only = true,
+                "--abort" => command.abort = true,
+                "-m" | "--message" => match args_iter.next() {
+                    Some(v) => command.message = Some(v),
+                    None => {
+                        println!("You must pass a message after {}", arg);
+                        process::exit(1);
+                    }
+                },
+                _ if arg.starts_with('-') || command.target.is_some() => {
+                    println!("{}", usage_msg);
+                    process::exit(1);
+                }
+                _ => command.target = Some(arg),
+            }
+        }
+
+        if command.target.is_none() && !command.abort {
+            println!("{}", usage_msg);
+            process::exit(1);
+        }
+
+        command
+    }
+
+    fn run(&self) {
+        if self.abort {
+            self.abort_merge();
+            return;
+        }
+
+        if read_merge_head(&self.root_path).is_some() {
+            let merging_msg = "You have not concluded your merge (MERGE_HEAD exists).".to_owned()
+                + "\n"
+                + "Commit the result or run `kvc merge --abort`.";
+
+            println!("{}", merging_msg);
+            process::exit(1);
+        }
+
+        let target_name = self.target.clone().unwrap_or_default();
+        let target_hash = match resolve_revision(&self.root_path, &target_name) {
+            Ok(Some(v)) => v,
+            Err(e) => {
+                println!("Could not resolve {}: {}", target_name, e);
+                process::exit(1);
+            }
+            Ok(None) => {
+                println!("{} is not something we can merge!", target_name);
+                process::exit(1);
+            }
+        };
+
+        let head_hash = match resolve_head(&self.root_path) {
+            Some(v) => v,
+            None => {
+                self.fast_forward(None, &target_hash, &target_name);
+                return;
+            }
+        };
+
+        if Commit::is_ancestor(&self.root_path, &target_hash, &head_hash) {
+            println!("Already up to date.");
+            return;
+        }
+
+        if !self.no_ff && Commit::is_ancestor(&self.root_path, &head_hash, &target_hash) {
+            self.fast_forward(Some(&head_hash), &target_hash, &target_name);
+            return;
+        }
+
+        if self.ff_only {
+            println!("Not possible to fast-forward, aborting.");
+            process::exit(1);
+        }
+
+        let config = read_committer_config(&self.root_path);
+        let status = self.ensure_clean_tree();
+
+        let base_hash = Commit::merge_base(&self.root_path, &head_hash, &target_hash);
+        let conflicts = self.merge_trees(
+            base_hash.as_deref(),
+            &head_hash,
+            &target_hash,
+            &status.untracked_paths(),
+        );
+
+        let message = match &self.message {
+            Some(v) => format!("{}\n", v.trim()),
+            None => self.default_message(&target_name, &target_hash),
+        };
+
+        if !conflicts.is_empty() {
+            let mut merge_message = message + "\n# Conflicts:\n";
+            for file_path in &conflicts {
+                merge_message += &format!("#\t{}\n", file_path);
+            }
+
+            self.write_merge_state(&target_hash, &merge_message);
+
+            println!("Automatic merge failed; fix conflicts and then commit the result.");
+            process::exit(1);
+        }
+
+        let staging_area = StagingArea::open(self.root_path.clone());
+        let commit = Commit {
+            tree: write_tree_from_index(&self.root_path, staging_area.entries()),
+            parents: vec![head_hash, target_hash],
+            author: Signature::now(&config.user),
+            committer: Signature::now(&config.user),
+            message,
+        };
+
+        let commit_hash = write_object(
+            &self.root_path,
+            ObjectKind::Commit,
+            commit.to_string().as_bytes(),
+        );
+
+        update_head(
+            &self.root_path,
+            &commit_hash,
+            &format!(
+                "merge {}: Merge made by the 'three-way' strategy.",
+                target_name
+            ),
+        );
+
+        println!("Merge made by the 'three-way' strategy.");
+    }
+}
+
+impl MergeCommand {
+    fn fast_forward(&self, head_hash: Option<&str>, target_hash: &str, target_name: &str) {
+        let target = Commit::read(&self.root_path, target_hash);
+
+        if let Err(conflicts) = checkout_tree(&self.root_path, &target.tree, false) {
+            println!("Your local changes to the following files would be overwritten by merge:");
+            for file_path in conflicts {
+                println!("\t\x1b[31m{}\x1b[0m", file_path);
+            }
+            println!("Commit your changes before you merge.");
+            process::exit(1);
+        }
+
+        update_head(
+            &self.root_path,
+            target_hash,
+            &format!("merge {}: Fast-forward", target_name),
+        );
+
+        if let Some(head_hash) = head_hash {
+            println!("Updating {}..{}", &head_hash[..7], &target_hash[..7]);
+        }
+        println!("Fast-forward");
+    }
+
+    /// Untracked files are fine, anything staged or modified is not.
+    fn ensure_clean_tree(&self) -> Status {
+        let status = match StatusCommand::collect(&self.root_path) {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Could not read the working tree: {}", e);
+                process::exit(1);
+            }
+        };
+
+        if !status.entries.is_empty() || !status.unmerged.is_empty() {
+            println!("Your local changes would be overwritten by merge.");
+            println!("Commit your changes before you merge.");
+            process::exit(1);
+        }
+
+        status
+    }
+
+    /// Applies the changes made between `base_hash` and `target_hash` on top of
+    /// the working tree and the index, returning the paths left in conflict.
+    /// Aborts before touching anything if that would overwrite `untracked` files.
+    fn merge_trees(
+        &self,
+        base_hash: Option<&str>,
+        head_hash: &str,
+        target_hash: &str,
+        untracked: &[&String],
+    ) -> Vec<String> {
+        let read_files = |hash: &str| {
+            let commit = Commit::read(&self.root_path, hash);
+            flatten_tree(&self.root_path, &commit.tree)
+        };
+
+        let base = base_hash.map(read_files).unwrap_or_default();
+        let ours = read_files(head_hash);
+        let theirs = read_files(target_hash);
+
+        let theirs_label = self.target.clone().unwrap_or_default();
+
+        let paths: BTreeSet<&String> = base
+            .keys()
+            .chain(ours.keys())
+            .chain(theirs.keys())
+            .collect();
+
+        // Theirs' version is written wherever ours has no file and theirs
+        // changed it, which would lose an untracked file at the same path.
+        let mut overwritten: Vec<&String> = Vec::new();
+
+        for file_path in &paths {
+            if ours.contains_key(*file_path) || theirs.get(*file_path) == base.get(*file_path) {
+                continue;
+            }
+
+            match overwrites_untracked(
+                &self.root_path,
+                untracked,
+                file_path,
+                theirs.get(*file_path),
+            ) {
+                Ok(true) => overwritten.push(file_path),
+                Ok(false) => (),
+                Err(e) => {
+                    println!("Could not read the working tree: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        if !overwritten.is_empty() {
+            println!("The following untracked working tree files would be overwritten by merge:");
+            for file_path in overwritten {
+                println!("\t\x1b[31m{}\x1b[0m", file_path);
+            }
+            println!("Please move or remove them before you merge.");
+            process::exit(1);
+        }
+
+        let mut staging_area = StagingArea::lock(self.root_path.clone());
+        let mut index = staging_area.entries().clone();
+        let mut conflicts: HashMap<String, ConflictEntry> = HashMap::new();
+
+        for file_path in paths {
+            let (base_hash, ours_hash, theirs_hash) = (
+                base.get(file_path),
+                ours.get(file_path),
+                theirs.get(file_path),
+            );
+            let full_path = self.root_path.join(file_path);
+
+            if ours_hash == theirs_hash || theirs_hash == base_hash {
+                continue;
+            }
+
+            if ours_hash == base_hash {
+                match theirs_hash {
+                    Some(hash) => {
+                        write_blob_to_file(&self.root_path, hash, &full_path);
+                        index.insert(file_path.clone(), hash.clone());
+                    }
+                    None => {
+                        if fs::remove_file(&full_path).is_ok() {
+                            remove_empty_parents(&full_path, &self.root_path);
+                        }
+                        index.remove(file_path);
+                    }
+                }
+                continue;
+            }
+
+            let conflict = ConflictEntry {
+                base: base_hash.cloned(),
+                ours: ours_hash.cloned(),
+                theirs: theirs_hash.cloned(),
+            };
+
+            let (ours_hash, theirs_hash) = match (ours_hash, theirs_hash) {
+                (Some(ours_hash), Some(theirs_hash)) => (ours_hash, theirs_hash),
+                (Some(_), None) | (None, Some(_)) => {
+                    if let Some(hash) = theirs_hash {
+                        write_blob_to_file(&self.root_path, hash, &full_path);
+                    }
+
+                    println!(
+                        "CONFLICT (modify/delete): {} deleted in {} and modified in the other side.",
+                        file_path,
+                        match ours_hash {
+                            Some(_) => &theirs_label[..],
+                            None => OURS_LABEL,
+                        }
+                    );
+
+                    index.remove(file_path);
+                    conflicts.insert(file_path.clone(), conflict);
+                    continue;
+                }
+                (None, None) => continue,
+            };
+
+            let base_content = base_hash
+                .map(|hash| self.read_blob(hash))
+                .unwrap_or_default();
+            let ours_content = self.read_blob(ours_hash);
+            let theirs_content = self.read_blob(theirs_hash);
+
+            let conflict_kind = match base_hash {
+                Some(_) => "content",
+                None => "add/add",
+            };
+
+            if is_binary(&ours_content) || is_binary(&theirs_content) {
+                println!(
+                    "CONFLICT ({}): Merge conflict in binary file {}",
+                    conflict_kind, file_path
+                );
+
+                index.remove(file_path);
+                conflicts.insert(file_path.clone(), conflict);
+                continue;
+            }
+
+            let result = merge_lines(
+                &base_content,
+                &ours_content,
+                &theirs_content,
+                OURS_LABEL,
+                &theirs_label,
+            );
+
+            match fs::write(&full_path, &result.content) {
+                Ok(v) => v,
+                Err(e) => panic!("Could not write file {}: {}", full_path.display(), e),
+            }
+
+            if result.conflicts == 0 {
+                let hash = write_object(&self.root_path, ObjectKind::Blob, &result.content);
+                index.insert(file_path.clone(), hash);
+                continue;
+            }
+
+            println!(
+                "CONFLICT ({}): Merge conflict in {}",
+                conflict_kind, file_path
+            );
+
+            index.remove(file_path);
+            conflicts.insert(file_path.clone(), conflict);
+        }
+
+        let mut conflicted_paths: Vec<String> = conflicts.keys().cloned().collect();
+        conflicted_paths.sort();
+
+        staging_area.set_entries(index, conflicts);
+        staging_area.save();
+
+        conflicted_paths
+    }
+
+    fn read_blob(&self, hash: &str) -> Vec<u8> {
+        match read_object_of_kind(&self.root_path, hash, ObjectKind::Blob) {
+            Ok(v) => v.content,
+            Err(e) => panic!("Could not read blob {}: {}", hash, e),
+        }
+    }
+
+    fn default_message(&self, target_name: &str, target_hash: &str) -> String {
+        match read_ref(&self.root_path, &branch_ref_name(target_name)) {
+            Some(_) => format!("Merge branch '{}'\n", target_name),
+            None => format!("Merge commit '{}'\n", &target_hash[..7]),
+        }
+    }
+
+    fn write_merge_state(&self, target_hash: &str, merge_message: &str) {
+        let files = [
+            (MERGE_HEAD_FILE_PATH, format!("{}\n", target_hash)),
+            (MERGE_MESSAGE_FILE_PATH, merge_message.to_owned()),
+        ];
+
+        for (file_path, content) in files {
+            match fs::write(self.root_path.join(file_path), content) {
+                Ok(v) => v,
+                Err(e) => panic!("Could not write {}: {}", file_path, e),
+            }
+        }
+    }
+
+    fn abort_merge(&self) {
+        if read_merge_head(&self.root_path).is_none() {
+            println!("There is no merge to abort (MERGE_HEAD missing).");
+            process::exit(1);
+        }
+
+        let head_hash = resolve_head(&self.root_path).unwrap_or_default();
+        let head_tree = Commit::read(&self.root_path, &head_hash).tree;
+        let head_files = flatten_tree(&self.root_path, &head_tree);
+
+        // Files the merge brought in are not tracked by HEAD, so checkout would keep them.
+        let staging_area = StagingArea::open(self.root_path.clone());
+        for file_path in staging_area
+            .entries()
+            .keys()
+            .chain(staging_area.conflicts().keys())
+        {
+            if !head_files.contains_key(file_path) {
+                fs::remove_file(self.root_path.join(file_path)).unwrap_or_default();
+            }
+        }
+
+        if let Err(conflicts) = checkout_tree(&self.root_path, &head_tree, true) {
+            panic!("Could not restore HEAD, conflicts left: {:?}", conflicts);
+        }
+
+        fs::remove_file(self.root_path.join(MERGE_HEAD_FILE_PATH)).unwrap_or_default();
+        fs::remove_file(self.root_path.join(MERGE_MESSAGE_FILE_PATH)).unwrap_or_default();
+    }
+}
@@ -0,0 +1,52 @@
+use std::{fs, path};
+
+use crate::{
+    object::{object_path, remove_loose_object},
+    reachability::{collect_roots, find_unreachable, mark_reachable, PruneOptions},
+    traits::ExecutableCommand,
+};
+
+pub struct PruneCommand {
+    root_path: path::PathBuf,
+    options: PruneOptions,
+}
+
+impl ExecutableCommand for PruneCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        PruneCommand {
+            root_path: root_folder,
+            options: PruneOptions::from_args(&args),
+        }
+    }
+
+    fn run(&self) {
+        let reachable = mark_reachable(&self.root_path, collect_roots(&self.root_path));
+        let unreachable = find_unreachable(&self.root_path, &reachable, self.options.expire);
+
+        let loose_expired: Vec<&String> = unreachable
+            .expired
+            .iter()
+            .filter(|hash| fs::exists(object_path(&self.root_path, hash)).unwrap_or_default())
+            .collect();
+
+        for hash in &loose_expired {
+            if self.options.dry_run {
+                println!("Would prune {}", hash);
+                continue;
+            }
+
+            remove_loose_object(&self.root_path, hash);
+        }
+
+        if self.options.dry_run {
+            println!(
+                "{} unreachable objects would be pruned, {} are newer than the grace period",
+                loose_expired.len(),
+                unreachable.recent.len()
+            );
+            return;
+        }
+
+        println!("Pruned {} unreachable objects", loose_expired.len());
+    }
+}
@@ -0,0 +1,57 @@
+use std::{collections::BTreeSet, path, process};
+
+use crate::{
+    object::{
+        list_loose_objects,
+        pack::{packed_hashes, verify_checksums},
+        repack_objects,
+    },
+    traits::ExecutableCommand,
+};
+
+pub struct RepackCommand {
+    root_path: path::PathBuf,
+}
+
+impl ExecutableCommand for RepackCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        if let Some(arg) = args.first() {
+            println!("Unknown repack argument: {}", arg);
+            process::exit(1);
+        }
+
+        RepackCommand {
+            root_path: root_folder,
+        }
+    }
+
+    fn run(&self) {
+        let loose_objects = list_loose_objects(&self.root_path);
+        if loose_objects.is_empty() {
+            println!("Nothing to repack, there are no loose objects!");
+            return;
+        }
+
+        // Objects from a damaged pack would be copied into the new one.
+        if let Some(corrupt) = verify_checksums(&self.root_path).first() {
+            println!(
+                "The pack file {} is corrupt, run `kvc fsck` for details.",
+                corrupt.display()
+            );
+            process::exit(1);
+        }
+
+        let hashes: BTreeSet<String> = loose_objects
+            .into_iter()
+            .chain(packed_hashes(&self.root_path))
+            .collect();
+        let hashes: Vec<String> = hashes.into_iter().collect();
+
+        let stats = repack_objects(&self.root_path, &hashes);
+
+        println!(
+            "Packed {} objects ({} stored as deltas)",
+            stats.objects, stats.deltas
+        );
+    }
+}
@@ -1,7 +1,8 @@
 use crate::{
     commands::ExecutableCommand,
+    ignore::IgnoreRules,
     staging_area::StagingArea,
-    utils::{get_file_path_relative_to_root, validate_path},
+    utils::{get_file_path_relative_to_root, is_inside_current_dir},
 };
 use std::{fs, path, process};
 
@@ -27,34 +28,50 @@ impl ExecutableCommand for RmCommand {
     }
 
     fn run(&self) {
-        let is_valid_path = validate_path(self.path.clone());
-        if !is_valid_path {
-            println!("The provided path is not valid to remove files from the index!");
-            process::exit(1);
-        }
-
         let path = &self.path;
-        let file_metadata = fs::metadata(path).unwrap();
+        let relative_path = get_file_path_relative_to_root(self.root_path.clone(), path.to_owned());
+
+        // The index is loaded once, updated in memory and written at the end.
+        let mut staging_area = StagingArea::lock(self.root_path.clone());
+
+        // Paths already gone from the working tree can still be removed from
+        // the index, as long as they are tracked.
+        let file_metadata = match fs::metadata(path) {
+            Ok(v) if is_inside_current_dir(path) => Some(v),
+            _ if is_inside_current_dir(path)
+                && !staging_area.remove_deleted(&relative_path).is_empty() =>
+            {
+                None
+            }
+            _ => {
+                println!("The provided path is not valid to remove files from the index!");
+                drop(staging_area);
+                process::exit(1);
+            }
+        };
 
-        if file_metadata.is_file() {
-            self.process_file(path);
-            process::exit(1);
+        match file_metadata {
+            Some(v) if v.is_file() => self.process_file(path, &mut staging_area),
+            Some(_) => {
+                let ignore_rules = IgnoreRules::load(&self.root_path);
+                self.process_dir(path, &ignore_rules, &mut staging_area);
+                staging_area.remove_deleted(&relative_path);
+            }
+            None => (),
         }
 
-        self.process_dir(path);
+        staging_area.save();
     }
 }
 
 impl RmCommand {
-    fn process_file(&self, path: &str) {
+    fn process_file(&self, path: &str, staging_area: &mut StagingArea) {
         let relative_path = get_file_path_relative_to_root(self.root_path.clone(), path.to_owned());
 
-        let mut staging_area = StagingArea::open(self.root_path.clone());
-
         staging_area.remove(relative_path);
     }
 
-    fn process_dir(&self, path: &str) {
+    fn process_dir(&self, path: &str, ignore_rules: &IgnoreRules, staging_area: &mut StagingArea) {
         let read_dir = match fs::read_dir(path) {
             Ok(v) => v,
             Err(_e) => panic!("Could not read directory!"),
@@ -66,12 +83,23 @@ impl RmCommand {
             let entry_metadata = fs::metadata(&entry_path).unwrap();
 
             let path = entry_path.to_str().unwrap();
+            let relative_path =
+                get_file_path_relative_to_root(self.root_path.clone(), path.to_owned());
+
+            if ignore_rules.excludes(
+                &relative_path,
+                entry_metadata.is_dir(),
+                staging_area.entries(),
+            ) {
+                continue;
+            }
+
             if entry_metadata.is_file() {
-                self.process_file(path);
+                self.process_file(path, staging_area);
                 continue;
             }
 
-            self.process_dir(path);
+            self.process_dir(path, ignore_rules, staging_area);
         }
     }
 }
@@ -1,140 +1,802 @@
 use crate::{
-    staging_area::StagingArea,
+    commit::Commit,
+    constants::ROOT_FOLDER_NAME,
+    ignore::IgnoreRules,
+    object::{hash_file, ObjectError},
+    parallel::{self, WalkEntry},
+    refs::{current_branch, resolve_head},
+    staging_area::{ConflictEntry, FileStat, StagingArea},
     traits::ExecutableCommand,
-    utils::{generate_hash, read_file},
+    tree::flatten_tree,
 };
 use std::{
-    collections::HashMap,
-    fs,
-    path::{self, PathBuf},
-    process,
+    collections::{BTreeSet, HashMap, VecDeque},
+    io::{self, IsTerminal, Write},
+    path, process,
 };
 
+/// Exit code of a status run that worked, whether the tree is clean or not.
+/// Errors (bad arguments, unreadable repository) exit with `STATUS_ERROR`.
+const STATUS_OK: i32 = 0;
+const STATUS_ERROR: i32 = 1;
+
+const NULL_MODE: &str = "000000";
+const FILE_MODE: &str = "100644";
+
 pub struct StatusCommand {
     root_path: path::PathBuf,
+    ignore_rules: IgnoreRules,
+    format: StatusFormat,
+    null_terminated: bool,
+}
+
+#[derive(PartialEq)]
+enum StatusFormat {
+    Long,
+    Short,
+    PorcelainV1,
+    PorcelainV2,
+    Json,
 }
 
-#[derive(Debug, Hash, PartialEq, Eq)]
-enum FileStatus {
-    Untracked,
-    Staged,
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) enum Change {
+    New,
     Modified,
+    Deleted,
+    /// Holds the path the file was renamed from.
+    Renamed(String),
+}
+
+/// A path whose index differs from HEAD (`staged`), whose working tree
+/// differs from the index (`unstaged`), or both.
+pub(super) struct StatusEntry {
+    pub path: String,
+    pub staged: Option<Change>,
+    pub unstaged: Option<Change>,
+}
+
+#[derive(Default)]
+struct WorktreeScan {
+    hashes: HashMap<String, String>,
+    untracked: BTreeSet<String>,
+    refreshed: HashMap<String, FileStat>,
+}
+
+pub(super) struct Status {
+    pub entries: Vec<StatusEntry>,
+    pub untracked: Vec<String>,
+    pub unmerged: Vec<String>,
+    pub head: HashMap<String, String>,
+    pub index: HashMap<String, String>,
+}
+
+impl Status {
+    pub fn is_clean(&self) -> bool {
+        self.entries.is_empty() && self.untracked.is_empty() && self.unmerged.is_empty()
+    }
+
+    /// Tracked paths whose working tree copy differs from the index.
+    pub fn dirty_paths(&self) -> Vec<&String> {
+        self.entries
+            .iter()
+            .filter_map(|v| match &v.unstaged {
+                Some(Change::Renamed(from)) => Some(from),
+                Some(_) => Some(&v.path),
+                None => None,
+            })
+            .collect()
+    }
+
+    /// Files in the working tree the index knows nothing about, including
+    /// the destination of unstaged renames.
+    pub fn untracked_paths(&self) -> Vec<&String> {
+        let renamed = self
+            .entries
+            .iter()
+            .filter(|v| matches!(v.unstaged, Some(Change::Renamed(_))))
+            .map(|v| &v.path);
+
+        self.untracked.iter().chain(renamed).collect()
+    }
 }
 
 impl ExecutableCommand for StatusCommand {
-    fn new(_args: Vec<String>, root_folder: path::PathBuf) -> Self {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        let usage_msg = "Usage:".to_owned()
+            + "\n\n"
+            + "kvc status [-s | --short] -> show the state of the working tree"
+            + "\n"
+            + "kvc status --porcelain[=v1|v2] [-z] -> stable output for scripts"
+            + "\n"
+            + "kvc status --json -> the same information as a JSON document";
+
+        let mut format = None;
+        let mut null_terminated = false;
+
+        for arg in &args {
+            let arg_format = match &arg[..] {
+                "-s" | "--short" => StatusFormat::Short,
+                "--porcelain" | "--porcelain=v1" | "--porcelain=1" => StatusFormat::PorcelainV1,
+                "--porcelain=v2" | "--porcelain=2" => StatusFormat::PorcelainV2,
+                "--json" => StatusFormat::Json,
+                "-z" => {
+                    null_terminated = true;
+                    continue;
+                }
+                _ => {
+                    println!("{}", usage_msg);
+                    process::exit(STATUS_ERROR);
+                }
+            };
+
+            if format.as_ref().is_some_and(|v| *v != arg_format) {
+                println!("Only one output format can be used at a time.");
+                process::exit(STATUS_ERROR);
+            }
+
+            format = Some(arg_format);
+        }
+
+        // Like git, -z alone implies the porcelain format.
+        let format = match (format, null_terminated) {
+            (Some(v), _) => v,
+            (None, true) => StatusFormat::PorcelainV1,
+            (None, false) => StatusFormat::Long,
+        };
+
         Self {
+            ignore_rules: IgnoreRules::load(&root_folder),
             root_path: root_folder,
+            format,
+            null_terminated,
         }
     }
 
     fn run(&self) {
-        let mut file_entries: HashMap<FileStatus, Vec<String>> = HashMap::from([
-            (FileStatus::Untracked, Vec::new()),
-            (FileStatus::Staged, Vec::new()),
-            (FileStatus::Modified, Vec::new()),
-        ]);
-
-        let root_path = self.root_path.to_str().unwrap();
+        let status = match Self::collect(&self.root_path) {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Could not read the working tree: {}", e);
+                process::exit(STATUS_ERROR);
+            }
+        };
 
-        self.process_dir(root_path, &mut file_entries);
+        let output = match self.format {
+            StatusFormat::Long => {
+                self.print_long(&status);
+                process::exit(STATUS_OK);
+            }
+            StatusFormat::Short => self.format_short(&status, io::stdout().is_terminal()),
+            StatusFormat::PorcelainV1 => self.format_short(&status, false),
+            StatusFormat::PorcelainV2 => self.format_porcelain_v2(&status),
+            StatusFormat::Json => self.format_json(&status),
+        };
 
-        let untracked_files = file_entries.get(&FileStatus::Untracked).unwrap();
-        let staged_files = file_entries.get(&FileStatus::Staged).unwrap();
-        let modified_files = file_entries.get(&FileStatus::Modified).unwrap();
+        let mut stdout = io::stdout().lock();
+        if let Err(e) = stdout.write_all(output.as_bytes()) {
+            panic!("Could not write the status! {}", e);
+        }
+    }
+}
 
-        if untracked_files.len() == 0 && staged_files.len() == 0 && modified_files.len() == 0 {
+impl StatusCommand {
+    fn print_long(&self, status: &Status) {
+        if status.is_clean() {
             println!("No changes made!");
-            process::exit(1);
+            return;
+        }
+
+        if !status.unmerged.is_empty() {
+            let staging_area = StagingArea::open(self.root_path.clone());
+
+            println!("Unmerged paths:");
+            println!("  (use \"kvc add <file>\" to mark resolution)");
+            for file in &status.unmerged {
+                let description = Self::describe_conflict(&staging_area.conflicts()[file]);
+                println!("\t\x1b[31m{:<16}{}\x1b[0m", description, file);
+            }
+            println!();
         }
 
-        if staged_files.len() > 0 {
+        let staged: Vec<(&String, &Change)> = status
+            .entries
+            .iter()
+            .filter_map(|v| v.staged.as_ref().map(|change| (&v.path, change)))
+            .collect();
+
+        if !staged.is_empty() {
             println!("Files to be commited:");
             println!("  (use \"kvc rm <file>\" to remove file for commit)");
-            for file in staged_files {
-                println!("\t\x1b[32m{}\x1b[0m", file);
+            for (file, change) in staged {
+                println!("\t\x1b[32m{}\x1b[0m", Self::describe_change(file, change));
             }
             println!();
         }
 
-        if modified_files.len() > 0 {
+        let unstaged: Vec<(&String, &Change)> = status
+            .entries
+            .iter()
+            .filter_map(|v| v.unstaged.as_ref().map(|change| (&v.path, change)))
+            .collect();
+
+        if !unstaged.is_empty() {
             println!("Files changed but not staged for commit:");
             println!("  (use \"kvc add <file>\" to update file for commit)");
-            for file in modified_files {
-                println!("\t\x1b[31m{}\x1b[0m", file);
+            println!("  (use \"kvc rm <file>\" to stage a deleted file)");
+            for (file, change) in unstaged {
+                println!("\t\x1b[31m{}\x1b[0m", Self::describe_change(file, change));
             }
             println!();
         }
 
-        if untracked_files.len() > 0 {
+        if !status.untracked.is_empty() {
             println!("Untracked files:");
             println!("  (use \"kvc add <file>\" to add file for commit)");
-            for file in untracked_files {
+            for file in &status.untracked {
                 println!("\t\x1b[31m{}\x1b[0m", file);
             }
             println!();
         }
     }
-}
 
-impl StatusCommand {
-    fn process_file(&self, path: &str, files: &mut HashMap<FileStatus, Vec<String>>) {
-        let file_content = read_file(path);
-        let file_hash = match generate_hash(&file_content) {
-            Ok(v) => v,
-            Err(_e) => panic!("Could not generate file hash!"),
-        };
+    fn line_end(&self) -> char {
+        match self.null_terminated {
+            true => '\0',
+            false => '\n',
+        }
+    }
 
-        let relative_path = PathBuf::from(path);
-        let str_relative_path = format!(
-            "{}",
-            relative_path
-                .strip_prefix(self.root_path.clone())
-                .unwrap()
-                .display()
-        );
+    /// Quotes paths that would break line based parsing, unless the output is
+    /// NUL terminated and paths are written verbatim.
+    fn format_path(&self, file_path: &str) -> String {
+        let needs_quotes = file_path
+            .chars()
+            .any(|v| v == '"' || v == '\\' || v.is_control());
+
+        if self.null_terminated || !needs_quotes {
+            return file_path.to_owned();
+        }
+
+        let mut quoted = String::from('"');
+        for character in file_path.chars() {
+            match character {
+                '"' => quoted.push_str("\\\""),
+                '\\' => quoted.push_str("\\\\"),
+                '\n' => quoted.push_str("\\n"),
+                '\t' => quoted.push_str("\\t"),
+                v if v.is_control() => quoted.push_str(&format!("\\{:03o}", v as u32)),
+                v => quoted.push(v),
+            }
+        }
+        quoted.push('"');
+
+        quoted
+    }
+
+    /// The two status letters of a path, X for the index and Y for the
+    /// working tree.
+    fn change_code(change: &Option<Change>) -> char {
+        match change {
+            None => '.',
+            Some(Change::New) => 'A',
+            Some(Change::Modified) => 'M',
+            Some(Change::Deleted) => 'D',
+            Some(Change::Renamed(_)) => 'R',
+        }
+    }
 
+    fn conflict_code(conflict: &ConflictEntry) -> &'static str {
+        match (&conflict.base, &conflict.ours, &conflict.theirs) {
+            (_, None, None) => "DD",
+            (None, Some(_), None) => "AU",
+            (Some(_), Some(_), None) => "UD",
+            (None, None, Some(_)) => "UA",
+            (Some(_), None, Some(_)) => "DU",
+            (None, Some(_), Some(_)) => "AA",
+            (Some(_), Some(_), Some(_)) => "UU",
+        }
+    }
+
+    /// Every tracked path with its status letters, sorted by path.
+    fn tracked_lines<'a>(
+        &self,
+        status: &'a Status,
+    ) -> Vec<(&'a String, String, Option<&'a String>)> {
         let staging_area = StagingArea::open(self.root_path.clone());
-        let staged_hash = staging_area.get(str_relative_path.clone());
-        if staged_hash == *"" {
-            let untracked_files = files.get_mut(&FileStatus::Untracked).unwrap();
-            untracked_files.push(str_relative_path.clone());
-            return;
+
+        let mut lines: Vec<(&String, String, Option<&String>)> = status
+            .entries
+            .iter()
+            .map(|v| {
+                let code = format!(
+                    "{}{}",
+                    Self::change_code(&v.staged),
+                    Self::change_code(&v.unstaged)
+                );
+                let renamed_from = match (&v.staged, &v.unstaged) {
+                    (Some(Change::Renamed(from)), _) | (_, Some(Change::Renamed(from))) => {
+                        Some(from)
+                    }
+                    _ => None,
+                };
+
+                (&v.path, code, renamed_from)
+            })
+            .collect();
+
+        lines.extend(status.unmerged.iter().map(|v| {
+            let code = Self::conflict_code(&staging_area.conflicts()[v]);
+
+            (v, code.to_owned(), None)
+        }));
+
+        lines.sort_by(|a, b| a.0.cmp(b.0));
+
+        lines
+    }
+
+    /// `XY path` lines, with `XY old -> new` for renames and `??` for
+    /// untracked files.
+    ///
+    /// With -z renames are written as `XY new\0old\0` instead, the new path
+    /// first like in git.
+    fn format_short(&self, status: &Status, color: bool) -> String {
+        let mut output = String::new();
+        let line_end = self.line_end();
+
+        for (file_path, code, renamed_from) in self.tracked_lines(status) {
+            let code = code.replace('.', " ");
+            let code = match color && !code.contains('U') && code != "DD" && code != "AA" {
+                true => format!("\x1b[32m{}\x1b[31m{}\x1b[0m", &code[..1], &code[1..]),
+                false => code,
+            };
+
+            match (renamed_from, self.null_terminated) {
+                (Some(from), true) => {
+                    output += &format!("{} {}{}{}{}", code, file_path, line_end, from, line_end)
+                }
+                (Some(from), false) => {
+                    output += &format!(
+                        "{} {} -> {}{}",
+                        code,
+                        self.format_path(from),
+                        self.format_path(file_path),
+                        line_end
+                    )
+                }
+                (None, _) => {
+                    output += &format!("{} {}{}", code, self.format_path(file_path), line_end)
+                }
+            }
         }
 
-        if staged_hash != file_hash {
-            let modified_files = files.get_mut(&FileStatus::Modified).unwrap();
-            modified_files.push(str_relative_path.clone());
+        for file_path in &status.untracked {
+            let code = match color {
+                true => "\x1b[31m??\x1b[0m",
+                false => "??",
+            };
+
+            output += &format!("{} {}{}", code, self.format_path(file_path), line_end);
         }
 
-        let staged_files = files.get_mut(&FileStatus::Staged).unwrap();
-        staged_files.push(str_relative_path.clone());
+        output
     }
 
-    fn process_dir(&self, path: &str, files: &mut HashMap<FileStatus, Vec<String>>) {
-        let read_dir = match fs::read_dir(path) {
-            Ok(v) => v,
-            Err(_e) => panic!("Could not read directory!"),
+    /// Git's porcelain v2 layout: `1` lines for changed paths, `2` for
+    /// renames, `u` for conflicts and `?` for untracked files, each carrying
+    /// the HEAD and index modes and hashes.
+    fn format_porcelain_v2(&self, status: &Status) -> String {
+        let staging_area = StagingArea::open(self.root_path.clone());
+        let mut output = String::new();
+        let line_end = self.line_end();
+        let null_hash = "0".repeat(64);
+
+        let branch = current_branch(&self.root_path).unwrap_or("(detached)".to_owned());
+        let head = resolve_head(&self.root_path).unwrap_or("(initial)".to_owned());
+        output += &format!("# branch.oid {}{}", head, line_end);
+        output += &format!("# branch.head {}{}", branch, line_end);
+
+        let mode_of = |hash: Option<&String>| match hash {
+            Some(_) => FILE_MODE,
+            None => NULL_MODE,
+        };
+
+        for (file_path, code, renamed_from) in self.tracked_lines(status) {
+            if let Some(conflict) = staging_area.conflicts().get(file_path) {
+                let stage_hash = |hash: &Option<String>| hash.clone().unwrap_or(null_hash.clone());
+                let stage_mode = |hash: &Option<String>| mode_of(hash.as_ref());
+                let worktree_mode = mode_of(
+                    self.root_path
+                        .join(file_path)
+                        .is_file()
+                        .then_some(file_path),
+                );
+
+                output += &format!(
+                    "u {} N... {} {} {} {} {} {} {} {}{}",
+                    code,
+                    stage_mode(&conflict.base),
+                    stage_mode(&conflict.ours),
+                    stage_mode(&conflict.theirs),
+                    worktree_mode,
+                    stage_hash(&conflict.base),
+                    stage_hash(&conflict.ours),
+                    stage_hash(&conflict.theirs),
+                    self.format_path(file_path),
+                    line_end
+                );
+                continue;
+            }
+
+            // A staged rename keeps its HEAD data under the old path, an
+            // unstaged one keeps the index data there too.
+            let head_path = renamed_from.unwrap_or(file_path);
+            let index_path = match code.ends_with('R') {
+                true => head_path,
+                false => file_path,
+            };
+            let head_hash = status.head.get(head_path);
+            let index_hash = status.index.get(index_path);
+            let worktree_mode = match code.ends_with('D') {
+                true => NULL_MODE,
+                false => FILE_MODE,
+            };
+
+            let fields = format!(
+                "{} N... {} {} {} {} {}",
+                code,
+                mode_of(head_hash),
+                mode_of(index_hash),
+                worktree_mode,
+                head_hash.unwrap_or(&null_hash),
+                index_hash.unwrap_or(&null_hash),
+            );
+
+            match renamed_from {
+                Some(from) => {
+                    let separator = match self.null_terminated {
+                        true => '\0',
+                        false => '\t',
+                    };
+
+                    output += &format!(
+                        "2 {} R100 {}{}{}{}",
+                        fields,
+                        self.format_path(file_path),
+                        separator,
+                        self.format_path(from),
+                        line_end
+                    );
+                }
+                None => {
+                    output += &format!("1 {} {}{}", fields, self.format_path(file_path), line_end)
+                }
+            }
+        }
+
+        for file_path in &status.untracked {
+            output += &format!("? {}{}", self.format_path(file_path), line_end);
+        }
+
+        output
+    }
+
+    fn format_json(&self, status: &Status) -> String {
+        let staging_area = StagingArea::open(self.root_path.clone());
+
+        let json_change = |change: &Option<Change>| match change {
+            None => "null".to_owned(),
+            Some(Change::New) => "{\"kind\":\"new\"}".to_owned(),
+            Some(Change::Modified) => "{\"kind\":\"modified\"}".to_owned(),
+            Some(Change::Deleted) => "{\"kind\":\"deleted\"}".to_owned(),
+            Some(Change::Renamed(from)) => {
+                format!("{{\"kind\":\"renamed\",\"from\":{}}}", json_string(from))
+            }
+        };
+
+        let entries: Vec<String> = status
+            .entries
+            .iter()
+            .map(|v| {
+                format!(
+                    "{{\"path\":{},\"staged\":{},\"unstaged\":{}}}",
+                    json_string(&v.path),
+                    json_change(&v.staged),
+                    json_change(&v.unstaged)
+                )
+            })
+            .collect();
+
+        let unmerged: Vec<String> = status
+            .unmerged
+            .iter()
+            .map(|v| {
+                let description = Self::describe_conflict(&staging_area.conflicts()[v]);
+
+                format!(
+                    "{{\"path\":{},\"conflict\":{}}}",
+                    json_string(v),
+                    json_string(description.trim_end_matches(':'))
+                )
+            })
+            .collect();
+
+        let untracked: Vec<String> = status.untracked.iter().map(|v| json_string(v)).collect();
+
+        let branch = match current_branch(&self.root_path) {
+            Some(v) => json_string(&v),
+            None => "null".to_owned(),
+        };
+        let head = match resolve_head(&self.root_path) {
+            Some(v) => json_string(&v),
+            None => "null".to_owned(),
+        };
+
+        format!(
+            "{{\"version\":1,\"branch\":{},\"head\":{},\"clean\":{},\"entries\":[{}],\"unmerged\":[{}],\"untracked\":[{}]}}\n",
+            branch,
+            head,
+            status.is_clean(),
+            entries.join(","),
+            unmerged.join(","),
+            untracked.join(",")
+        )
+    }
+
+    /// Compares HEAD's tree against the index and the index against the
+    /// working tree, pairing deletions with additions of the same content as
+    /// renames.
+    pub(super) fn collect(root_path: &path::Path) -> Result<Status, ObjectError> {
+        let command = StatusCommand {
+            root_path: root_path.to_path_buf(),
+            ignore_rules: IgnoreRules::load(root_path),
+            format: StatusFormat::Long,
+            null_terminated: false,
+        };
+
+        let mut staging_area = StagingArea::open(root_path.to_path_buf());
+
+        let head = match resolve_head(root_path) {
+            Some(hash) => flatten_tree(root_path, &Commit::read(root_path, &hash).tree),
+            None => HashMap::new(),
         };
 
-        for entry in read_dir {
-            let read_entry = entry.unwrap();
-            let entry_path = read_entry.path();
-            let entry_relative_path = entry_path.strip_prefix(self.root_path.clone()).unwrap();
+        let mut scan = WorktreeScan::default();
+        command.process_dir(&staging_area, &mut scan)?;
 
-            if entry_relative_path.starts_with(".kvc") {
+        let index = staging_area.entries().clone();
+        let conflicts = staging_area.conflicts().clone();
+        staging_area.refresh(scan.refreshed);
+
+        let worktree = scan.hashes;
+
+        let mut staged: HashMap<String, Change> = HashMap::new();
+        for file_path in head.keys().chain(index.keys()) {
+            if conflicts.contains_key(file_path) {
                 continue;
             }
 
-            let entry_metadata = fs::metadata(&entry_path).unwrap();
+            let change = match (head.get(file_path), index.get(file_path)) {
+                (None, Some(_)) => Change::New,
+                (Some(_), None) => Change::Deleted,
+                (Some(old), Some(new)) if old != new => Change::Modified,
+                _ => continue,
+            };
 
-            let path = format!("{}", entry_path.display());
-            if entry_metadata.is_file() {
-                self.process_file(&path, files);
+            staged.insert(file_path.clone(), change);
+        }
+
+        let mut unstaged: HashMap<String, Change> = HashMap::new();
+        for (file_path, hash) in &index {
+            if conflicts.contains_key(file_path) {
                 continue;
             }
 
-            self.process_dir(&path, files);
+            match worktree.get(file_path) {
+                None => unstaged.insert(file_path.clone(), Change::Deleted),
+                Some(v) if v != hash => unstaged.insert(file_path.clone(), Change::Modified),
+                _ => continue,
+            };
+        }
+
+        let mut untracked: BTreeSet<String> = scan
+            .untracked
+            .into_iter()
+            .filter(|v| !conflicts.contains_key(v))
+            .collect();
+
+        Self::detect_staged_renames(&head, &index, &mut staged);
+        Self::detect_unstaged_renames(root_path, &index, &staged, &mut unstaged, &mut untracked)?;
+
+        let paths: BTreeSet<&String> = staged.keys().chain(unstaged.keys()).collect();
+        let entries = paths
+            .into_iter()
+            .map(|v| StatusEntry {
+                path: v.clone(),
+                staged: staged.get(v).cloned(),
+                unstaged: unstaged.get(v).cloned(),
+            })
+            .collect();
+
+        let mut unmerged: Vec<String> = conflicts.keys().cloned().collect();
+        unmerged.sort();
+
+        Ok(Status {
+            entries,
+            untracked: untracked.into_iter().collect(),
+            unmerged,
+            index,
+            head,
+        })
+    }
+
+    /// A path deleted from the index and a new one holding the same blob are
+    /// reported as a single rename.
+    fn detect_staged_renames(
+        head: &HashMap<String, String>,
+        index: &HashMap<String, String>,
+        staged: &mut HashMap<String, Change>,
+    ) {
+        let mut deleted: Vec<String> = staged
+            .iter()
+            .filter(|(_, change)| **change == Change::Deleted)
+            .map(|(file_path, _)| file_path.clone())
+            .collect();
+        deleted.sort();
+
+        let mut added: Vec<String> = staged
+            .iter()
+            .filter(|(_, change)| **change == Change::New)
+            .map(|(file_path, _)| file_path.clone())
+            .collect();
+        added.sort();
+
+        for old_path in deleted {
+            let position = added.iter().position(|v| index[v] == head[&old_path]);
+
+            if let Some(position) = position {
+                let new_path = added.remove(position);
+                staged.remove(&old_path);
+                staged.insert(new_path, Change::Renamed(old_path));
+            }
+        }
+    }
+
+    /// A tracked file missing from the working tree and an untracked one with
+    /// the same content are reported as a rename not staged yet.
+    fn detect_unstaged_renames(
+        root_path: &path::Path,
+        index: &HashMap<String, String>,
+        staged: &HashMap<String, Change>,
+        unstaged: &mut HashMap<String, Change>,
+        untracked: &mut BTreeSet<String>,
+    ) -> Result<(), ObjectError> {
+        let mut deleted: Vec<String> = unstaged
+            .iter()
+            .filter(|(file_path, change)| {
+                **change == Change::Deleted && !staged.contains_key(*file_path)
+            })
+            .map(|(file_path, _)| file_path.clone())
+            .collect();
+        deleted.sort();
+
+        // Untracked files are only hashed when there is a deletion to pair,
+        // and each of them once. Files with the same content are paired in
+        // path order.
+        if deleted.is_empty() {
+            return Ok(());
+        }
+
+        let candidates: Vec<String> = untracked.iter().cloned().collect();
+        let hashes = parallel::map(
+            &candidates,
+            parallel::thread_count(root_path),
+            |file_path| hash_file(&root_path.join(file_path).to_string_lossy()),
+        );
+
+        let mut by_hash: HashMap<String, VecDeque<String>> = HashMap::new();
+        for (file_path, hash) in candidates.into_iter().zip(hashes) {
+            by_hash.entry(hash?).or_default().push_back(file_path);
+        }
+
+        for old_path in deleted {
+            let new_path = match by_hash.get_mut(&index[&old_path]) {
+                Some(paths) => paths.pop_front(),
+                None => None,
+            };
+
+            if let Some(new_path) = new_path {
+                untracked.remove(&new_path);
+                unstaged.remove(&old_path);
+                unstaged.insert(new_path, Change::Renamed(old_path));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn describe_change(file: &str, change: &Change) -> String {
+        match change {
+            Change::New => format!("new file:   {}", file),
+            Change::Modified => format!("modified:   {}", file),
+            Change::Deleted => format!("deleted:    {}", file),
+            Change::Renamed(from) => format!("renamed:    {} -> {}", from, file),
+        }
+    }
+
+    fn describe_conflict(conflict: &ConflictEntry) -> &'static str {
+        match (&conflict.base, &conflict.ours, &conflict.theirs) {
+            (_, Some(_), None) => "deleted by them:",
+            (_, None, Some(_)) => "deleted by us:",
+            (None, Some(_), Some(_)) => "both added:",
+            _ => "both modified:",
+        }
+    }
+
+    /// Walks the working tree, hashing the tracked files whose stat data
+    /// doesn't match the index and listing the untracked ones not ignored.
+    /// Directories and hashes are spread over the worker threads.
+    fn process_dir(
+        &self,
+        staging_area: &StagingArea,
+        scan: &mut WorktreeScan,
+    ) -> Result<(), ObjectError> {
+        let threads = parallel::thread_count(&self.root_path);
+        let index = staging_area.entries();
+
+        let files = parallel::walk(&self.root_path, "", threads, |relative_path, is_dir| {
+            relative_path == ROOT_FOLDER_NAME
+                || self.ignore_rules.excludes(relative_path, is_dir, index)
+        });
+
+        let mut to_hash: Vec<WalkEntry> = Vec::new();
+        for file in files {
+            match index.get(&file.relative_path) {
+                None => {
+                    scan.untracked.insert(file.relative_path);
+                }
+                Some(v) if staging_area.is_unchanged(&file.relative_path, &file.metadata) => {
+                    scan.hashes.insert(file.relative_path, v.clone());
+                }
+                Some(_) => to_hash.push(file),
+            }
+        }
+
+        let hashes = parallel::map(&to_hash, threads, |file| {
+            hash_file(&file.path.to_string_lossy())
+        });
+
+        for (file, file_hash) in to_hash.into_iter().zip(hashes) {
+            let file_hash = file_hash?;
+            if index.get(&file.relative_path) == Some(&file_hash) {
+                scan.refreshed.insert(
+                    file.relative_path.clone(),
+                    FileStat::from_metadata(&file.metadata),
+                );
+            }
+            scan.hashes.insert(file.relative_path, file_hash);
+        }
+
+        Ok(())
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from('"');
+
+    for character in value.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            v if v.is_control() => escaped.push_str(&format!("\\u{:04x}", v as u32)),
+            v => escaped.push(v),
         }
     }
+    escaped.push('"');
+
+    escaped
 }
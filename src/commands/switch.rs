@@ -0,0 +1,374 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs, path, process,
+};
+
+use crate::{
+    commands::status::StatusCommand,
+    commit::Commit,
+    constants::{MERGE_HEAD_FILE_PATH, MERGE_MESSAGE_FILE_PATH},
+    object::{hash_file, read_object_of_kind, ObjectError, ObjectKind},
+    refs::{
+        branch_ref_name, checkout_branch_head, current_branch, detach_head, is_valid_branch_name,
+        read_ref, ref_name_conflicts, remove_empty_parents, resolve_head, resolve_revision,
+        write_ref,
+    },
+    staging_area::StagingArea,
+    traits::ExecutableCommand,
+    tree::flatten_tree,
+};
+
+pub struct SwitchCommand {
+    root_path: path::PathBuf,
+    target: Option<String>,
+    new_branch: Option<String>,
+    detach: bool,
+    force: bool,
+}
+
+impl ExecutableCommand for SwitchCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        let usage_msg = "Usage:".to_owned()
+            + "\n\n"
+            + "kvc switch <branch> -> switch to an existing branch"
+            + "\n"
+            + "kvc switch (-c|--create) <new-branch> [<start>] -> create a branch and switch to it"
+            + "\n"
+            + "kvc switch (-d|--detach) <commit> -> detach HEAD at a commit"
+            + "\n\n"
+            + "Pass -f or --force to discard local changes that would be overwritten.";
+
+        let mut command = SwitchCommand {
+            root_path: root_folder,
+            target: None,
+            new_branch: None,
+            detach: false,
+            force: false,
+        };
+
+        let mut args_iter = args.into_iter();
+
+        while let Some(arg) = args_iter.next() {
+            match &arg[..] {
+                "-c" | "--create" => command.new_branch = args_iter.next(),
+                "-d" | "--detach" => command.detach = true,
+                "-f" | "--force" | "--discard-changes" => command.force = true,
+                _ if arg.starts_with('-') || command.target.is_some() => {
+                    println!("{}", usage_msg);
+                    process::exit(1);
+                }
+                _ => command.target = Some(arg),
+            }
+        }
+
+        if command.target.is_none() && command.new_branch.is_none() {
+            println!("{}", usage_msg);
+            process::exit(1);
+        }
+
+        command
+    }
+
+    fn run(&self) {
+        if let Some(new_branch) = &self.new_branch {
+            self.create_and_switch(new_branch);
+            return;
+        }
+
+        let target = self.target.clone().unwrap_or_default();
+        let is_branch = read_ref(&self.root_path, &branch_ref_name(&target)).is_some();
+
+        if is_branch && !self.detach {
+            self.switch_to_branch(&target);
+            return;
+        }
+
+        if !self.detach {
+            println!("{} is not a branch!", target);
+            println!(
+                "Use `kvc switch --detach {}` to inspect it with a detached HEAD.",
+                target
+            );
+            process::exit(1);
+        }
+
+        self.switch_to_commit(&target);
+    }
+}
+
+impl SwitchCommand {
+    pub(super) fn with_options(
+        root_path: path::PathBuf,
+        target: Option<String>,
+        new_branch: Option<String>,
+        detach: bool,
+        force: bool,
+    ) -> SwitchCommand {
+        SwitchCommand {
+            root_path,
+            target,
+            new_branch,
+            detach,
+            force,
+        }
+    }
+
+    fn create_and_switch(&self, new_branch: &str) {
+        if !is_valid_branch_name(new_branch) {
+            println!("{} is not a valid branch name!", new_branch);
+            process::exit(1);
+        }
+
+        if ref_name_conflicts(&self.root_path, &branch_ref_name(new_branch)) {
+            println!(
+                "A branch named {} already exists or conflicts with another branch!",
+                new_branch
+            );
+            process::exit(1);
+        }
+
+        let start_name = self.target.clone().unwrap_or("HEAD".to_owned());
+        let start_hash = match resolve_revision(&self.root_path, &start_name) {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Could not resolve {}: {}", start_name, e);
+                process::exit(1);
+            }
+        };
+
+        if start_hash.is_none() && self.target.is_some() {
+            println!("Not a valid commit to start the branch: {}", start_name);
+            process::exit(1);
+        }
+
+        if let Some(start_hash) = &start_hash {
+            self.update_working_tree(start_hash);
+
+            write_ref(
+                &self.root_path,
+                &branch_ref_name(new_branch),
+                start_hash,
+                &format!("branch: Created from {}", start_name),
+            );
+        }
+
+        checkout_branch_head(
+            &self.root_path,
+            new_branch,
+            &self.reflog_message(new_branch),
+        );
+
+        println!("Switched to a new branch '{}'", new_branch);
+    }
+
+    fn switch_to_branch(&self, branch: &str) {
+        if current_branch(&self.root_path).as_deref() == Some(branch) {
+            println!("Already on '{}'", branch);
+            return;
+        }
+
+        let branch_hash = read_ref(&self.root_path, &branch_ref_name(branch)).unwrap_or_default();
+        self.update_working_tree(&branch_hash);
+
+        checkout_branch_head(&self.root_path, branch, &self.reflog_message(branch));
+
+        println!("Switched to branch '{}'", branch);
+    }
+
+    fn switch_to_commit(&self, revision: &str) {
+        let commit_hash = match resolve_revision(&self.root_path, revision) {
+            Ok(Some(v)) => v,
+            Err(e) => {
+                println!("Could not resolve {}: {}", revision, e);
+                process::exit(1);
+            }
+            Ok(None) => {
+                println!("Not a valid commit: {}", revision);
+                process::exit(1);
+            }
+        };
+
+        self.update_working_tree(&commit_hash);
+
+        detach_head(
+            &self.root_path,
+            &commit_hash,
+            &self.reflog_message(&commit_hash),
+        );
+
+        let commit = Commit::read(&self.root_path, &commit_hash);
+        println!(
+            "HEAD is now at {} {} (detached)",
+            &commit_hash[..7],
+            commit.summary()
+        );
+    }
+
+    fn update_working_tree(&self, commit_hash: &str) {
+        let commit = Commit::read(&self.root_path, commit_hash);
+
+        if let Err(conflicts) = checkout_tree(&self.root_path, &commit.tree, self.force) {
+            println!("Your local changes to the following files would be overwritten:");
+            for file_path in conflicts {
+                println!("\t\x1b[31m{}\x1b[0m", file_path);
+            }
+            println!("Commit your changes or use --force to discard them.");
+            process::exit(1);
+        }
+
+        // A forced switch throws away the merge in progress along with the
+        // conflicts, so the next commit must not record it as a parent.
+        if self.force {
+            fs::remove_file(self.root_path.join(MERGE_HEAD_FILE_PATH)).unwrap_or_default();
+            fs::remove_file(self.root_path.join(MERGE_MESSAGE_FILE_PATH)).unwrap_or_default();
+        }
+    }
+
+    fn reflog_message(&self, target: &str) -> String {
+        let from = match current_branch(&self.root_path) {
+            Some(v) => v,
+            None => resolve_head(&self.root_path).unwrap_or_default(),
+        };
+
+        format!("checkout: moving from {} to {}", from, target)
+    }
+}
+
+/// Makes the working tree and the index match `tree_hash`, keeping local
+/// changes to files that are the same on both sides. When `force` is false
+/// and any of those changes would be lost, nothing is touched and the
+/// conflicting paths are returned instead.
+pub(super) fn checkout_tree(
+    root_path: &path::Path,
+    tree_hash: &str,
+    force: bool,
+) -> Result<(), Vec<String>> {
+    let current = match resolve_head(root_path) {
+        Some(head) => flatten_tree(root_path, &Commit::read(root_path, &head).tree),
+        None => HashMap::new(),
+    };
+    let target = flatten_tree(root_path, tree_hash);
+
+    let mut staging_area = StagingArea::lock(root_path.to_path_buf());
+    let mut index = staging_area.entries().clone();
+
+    if !force && !staging_area.conflicts().is_empty() {
+        let mut unmerged: Vec<String> = staging_area.conflicts().keys().cloned().collect();
+        unmerged.sort();
+
+        return Err(unmerged);
+    }
+
+    let status = match StatusCommand::collect(root_path) {
+        Ok(v) => v,
+        Err(e) => exit_unreadable_worktree(staging_area, e),
+    };
+    let modified = status.dirty_paths();
+    let untracked = status.untracked_paths();
+
+    let mut paths: BTreeSet<&String> = current.keys().chain(target.keys()).collect();
+    if force {
+        paths.extend(index.keys());
+    }
+
+    let changed: Vec<String> = paths
+        .into_iter()
+        .filter(|v| force || current.get(*v) != target.get(*v))
+        .cloned()
+        .collect();
+
+    if !force {
+        let mut conflicts: Vec<String> = Vec::new();
+
+        for file_path in &changed {
+            let is_dirty =
+                index.get(file_path) != current.get(file_path) || modified.contains(&file_path);
+            let matches_target =
+                index.get(file_path) == target.get(file_path) && !modified.contains(&file_path);
+            let loses_untracked =
+                match overwrites_untracked(root_path, &untracked, file_path, target.get(file_path))
+                {
+                    Ok(v) => v,
+                    Err(e) => exit_unreadable_worktree(staging_area, e),
+                };
+
+            if (is_dirty && !matches_target) || loses_untracked {
+                conflicts.push(file_path.clone());
+            }
+        }
+
+        if !conflicts.is_empty() {
+            return Err(conflicts);
+        }
+    }
+
+    for file_path in &changed {
+        if target.contains_key(file_path) {
+            continue;
+        }
+
+        let full_path = root_path.join(file_path);
+        if current.contains_key(file_path) && fs::remove_file(&full_path).is_ok() {
+            remove_empty_parents(&full_path, root_path);
+        }
+        index.remove(file_path);
+    }
+
+    for file_path in &changed {
+        let hash = match target.get(file_path) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        write_blob_to_file(root_path, hash, &root_path.join(file_path));
+        index.insert(file_path.clone(), hash.clone());
+    }
+
+    staging_area.set_entries(index, HashMap::new());
+    staging_area.save();
+
+    Ok(())
+}
+
+/// Whether writing `hash` at `file_path` would lose the content of an
+/// untracked file already sitting there.
+pub(super) fn overwrites_untracked(
+    root_path: &path::Path,
+    untracked: &[&String],
+    file_path: &String,
+    hash: Option<&String>,
+) -> Result<bool, ObjectError> {
+    match hash {
+        Some(hash) if untracked.contains(&file_path) => {
+            Ok(*hash != hash_file(&root_path.join(file_path).to_string_lossy())?)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Reports a working tree file that couldn't be read, releasing the index
+/// lock first since exiting skips destructors.
+fn exit_unreadable_worktree(staging_area: StagingArea, error: ObjectError) -> ! {
+    drop(staging_area);
+    println!("Could not read the working tree: {}", error);
+    process::exit(1);
+}
+
+pub(super) fn write_blob_to_file(root_path: &path::Path, hash: &str, file_path: &path::Path) {
+    let blob = match read_object_of_kind(root_path, hash, ObjectKind::Blob) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not read blob {}: {}", hash, e),
+    };
+
+    if let Some(parent) = file_path.parent() {
+        match fs::create_dir_all(parent) {
+            Ok(v) => v,
+            Err(e) => panic!("Could not create directory {}: {}", parent.display(), e),
+        }
+    }
+
+    match fs::write(file_path, &blob.content) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not write file {}: {}", file_path.display(), e),
+    }
+}
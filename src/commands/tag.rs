@@ -0,0 +1,242 @@
+use std::{path, process};
+
+use crate::{
+    commands::commit::read_committer_config,
+    commit::Signature,
+    object::{read_object, write_object, ObjectKind},
+    refs::{
+        delete_ref, is_valid_branch_name, list_tags, read_ref, ref_name_conflicts, resolve_object,
+        tag_ref_name, write_ref,
+    },
+    tag::Tag,
+    traits::ExecutableCommand,
+    utils::glob_match,
+};
+
+pub struct TagCommand {
+    root_path: path::PathBuf,
+    action: TagAction,
+}
+
+enum TagAction {
+    List {
+        patterns: Vec<String>,
+        annotations: bool,
+    },
+    Create {
+        name: String,
+        target: Option<String>,
+        message: Option<String>,
+        force: bool,
+    },
+    Delete {
+        names: Vec<String>,
+    },
+}
+
+impl ExecutableCommand for TagCommand {
+    fn new(args: Vec<String>, root_folder: path::PathBuf) -> Self {
+        let usage_msg = "Usage:".to_owned()
+            + "\n\n"
+            + "kvc tag [-l [<pattern>...]] [-n] -> list the tags, optionally filtered by globs"
+            + "\n"
+            + "kvc tag [-f] <name> [<commit>] -> create a lightweight tag"
+            + "\n"
+            + "kvc tag [-f] -a <name> -m <message> [<commit>] -> create an annotated tag"
+            + "\n"
+            + "kvc tag -d <name>... -> delete tags";
+
+        let mut list = false;
+        let mut annotations = false;
+        let mut annotated = false;
+        let mut delete = false;
+        let mut force = false;
+        let mut message = None;
+        let mut values: Vec<String> = Vec::new();
+
+        let mut args_iter = args.into_iter();
+
+        while let Some(arg) = args_iter.next() {
+            match &arg[..] {
+                "-l" | "--list" => list = true,
+                "-n" => annotations = true,
+                "-a" | "--annotate" => annotated = true,
+                "-d" | "--delete" => delete = true,
+                "-f" | "--force" => force = true,
+                "-m" | "--message" => match args_iter.next() {
+                    Some(v) => message = Some(v),
+                    None => {
+                        println!("You must pass a message after {}", arg);
+                        process::exit(1);
+                    }
+                },
+                _ if arg.starts_with('-') => {
+                    println!("{}", usage_msg);
+                    process::exit(1);
+                }
+                _ => values.push(arg),
+            }
+        }
+
+        if annotated && message.is_none() {
+            println!("Annotated tags need a message, pass it with -m <message>.");
+            process::exit(1);
+        }
+
+        let action = match (list || annotations || values.is_empty(), delete) {
+            (_, true) if !values.is_empty() => TagAction::Delete { names: values },
+            (true, false) if message.is_none() => TagAction::List {
+                patterns: values,
+                annotations,
+            },
+            (false, false) if values.len() <= 2 => TagAction::Create {
+                name: values[0].clone(),
+                target: values.get(1).cloned(),
+                message,
+                force,
+            },
+            _ => {
+                println!("{}", usage_msg);
+                process::exit(1);
+            }
+        };
+
+        TagCommand {
+            root_path: root_folder,
+            action,
+        }
+    }
+
+    fn run(&self) {
+        match &self.action {
+            TagAction::List {
+                patterns,
+                annotations,
+            } => self.list(patterns, *annotations),
+            TagAction::Create {
+                name,
+                target,
+                message,
+                force,
+            } => self.create(name, target, message, *force),
+            TagAction::Delete { names } => self.delete(names),
+        }
+    }
+}
+
+impl TagCommand {
+    fn list(&self, patterns: &[String], annotations: bool) {
+        let tags = list_tags(&self.root_path);
+        let name_width = tags.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+
+        for (name, hash) in &tags {
+            if !patterns.is_empty() && !patterns.iter().any(|v| glob_match(v, name)) {
+                continue;
+            }
+
+            if !annotations {
+                println!("{}", name);
+                continue;
+            }
+
+            let summary = match read_object(&self.root_path, hash) {
+                Ok(object) if object.kind == ObjectKind::Tag => Tag::parse(&object.text())
+                    .map(|tag| tag.summary().to_owned())
+                    .unwrap_or_default(),
+                _ => String::new(),
+            };
+
+            println!(
+                "{}",
+                format!("{:<name_width$}  {}", name, summary).trim_end()
+            );
+        }
+    }
+
+    fn create(&self, name: &str, target: &Option<String>, message: &Option<String>, force: bool) {
+        if !is_valid_branch_name(name) {
+            println!("{} is not a valid tag name!", name);
+            process::exit(1);
+        }
+
+        let ref_name = tag_ref_name(name);
+        let existing = read_ref(&self.root_path, &ref_name);
+
+        if ref_name_conflicts(&self.root_path, &ref_name) && !(force && existing.is_some()) {
+            println!("The tag {} already exists!", name);
+            process::exit(1);
+        }
+
+        let target_name = target.clone().unwrap_or("HEAD".to_owned());
+        let target_hash = match resolve_object(&self.root_path, &target_name) {
+            Ok(Some(v)) => v,
+            Err(e) => {
+                println!("Could not resolve {}: {}", target_name, e);
+                process::exit(1);
+            }
+            Ok(None) => {
+                println!("Not a valid object to tag: {}", target_name);
+                process::exit(1);
+            }
+        };
+
+        let tag_hash = match message {
+            Some(message) => self.write_tag_object(name, &target_hash, message),
+            None => target_hash,
+        };
+
+        write_ref(
+            &self.root_path,
+            &ref_name,
+            &tag_hash,
+            &format!("tag: {}", name),
+        );
+
+        match existing {
+            Some(old_hash) => println!("Updated tag '{}' (was {})", name, &old_hash[..7]),
+            None => println!("Created tag '{}' at {}", name, &tag_hash[..7]),
+        }
+    }
+
+    fn write_tag_object(&self, name: &str, target_hash: &str, message: &str) -> String {
+        let config = read_committer_config(&self.root_path);
+
+        let kind = match read_object(&self.root_path, target_hash) {
+            Ok(v) => v.kind,
+            Err(e) => panic!("Could not read object {}: {}", target_hash, e),
+        };
+
+        let tag = Tag {
+            object: target_hash.to_owned(),
+            kind,
+            name: name.to_owned(),
+            tagger: Signature::now(&config.user),
+            message: format!("{}\n", message.trim()),
+        };
+
+        write_object(&self.root_path, ObjectKind::Tag, tag.to_string().as_bytes())
+    }
+
+    fn delete(&self, names: &[String]) {
+        let mut has_errors = false;
+
+        for name in names {
+            let ref_name = tag_ref_name(name);
+
+            match read_ref(&self.root_path, &ref_name) {
+                Some(hash) => {
+                    delete_ref(&self.root_path, &ref_name);
+                    println!("Deleted tag '{}' (was {})", name, &hash[..7]);
+                }
+                None => {
+                    println!("The tag {} does not exist!", name);
+                    has_errors = true;
+                }
+            }
+        }
+
+        if has_errors {
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt, path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    config::User,
+    object::{read_object_of_kind, ObjectKind},
+};
+
+const DEFAULT_TIMEZONE: &str = "+0000";
+
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    pub timestamp: u64,
+    pub timezone: String,
+}
+
+pub struct Commit {
+    pub tree: String,
+    pub parents: Vec<String>,
+    pub author: Signature,
+    pub committer: Signature,
+    pub message: String,
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} <{}> {} {}",
+            self.name, self.email, self.timestamp, self.timezone
+        )
+    }
+}
+
+impl fmt::Display for Commit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "tree {}", self.tree)?;
+        for parent in &self.parents {
+            writeln!(f, "parent {}", parent)?;
+        }
+        writeln!(f, "author {}", self.author)?;
+        writeln!(f, "committer {}", self.committer)?;
+        writeln!(f)?;
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Signature {
+    pub fn now(user: &User) -> Signature {
+        let timestamp = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(v) => v.as_secs(),
+            Err(e) => panic!("System clock is before the unix epoch! {}", e),
+        };
+
+        Signature {
+            name: user.name.clone(),
+            email: user.email.clone(),
+            timestamp,
+            timezone: DEFAULT_TIMEZONE.to_owned(),
+        }
+    }
+
+    pub fn parse(str: &str) -> Option<Signature> {
+        let (name, rest) = str.split_once(" <")?;
+        let (email, rest) = rest.split_once("> ")?;
+        let mut splitted = rest.split(' ');
+
+        let timestamp = splitted.next()?.parse::<u64>().ok()?;
+        let timezone = splitted.next().unwrap_or(DEFAULT_TIMEZONE);
+
+        Some(Signature {
+            name: name.to_owned(),
+            email: email.to_owned(),
+            timestamp,
+            timezone: timezone.to_owned(),
+        })
+    }
+}
+
+impl Commit {
+    pub fn parse(content: &str) -> Option<Commit> {
+        let (headers, message) = content.split_once("\n\n").unwrap_or((content, ""));
+
+        let mut tree = None;
+        let mut parents = Vec::new();
+        let mut author = None;
+        let mut committer = None;
+
+        for line in headers.lines() {
+            let (key, value) = line.split_once(' ')?;
+
+            match key {
+                "tree" => tree = Some(value.to_owned()),
+                "parent" => parents.push(value.to_owned()),
+                "author" => author = Signature::parse(value),
+                "committer" => committer = Signature::parse(value),
+                _ => continue,
+            }
+        }
+
+        Some(Commit {
+            tree: tree?,
+            parents,
+            author: author?,
+            committer: committer?,
+            message: message.to_owned(),
+        })
+    }
+
+    pub fn read(root_path: &path::Path, hash: &str) -> Commit {
+        let object = match read_object_of_kind(root_path, hash, ObjectKind::Commit) {
+            Ok(v) => v,
+            Err(e) => panic!("Could not read commit {}: {}", hash, e),
+        };
+
+        match Commit::parse(&object.text()) {
+            Some(v) => v,
+            None => panic!("The commit {} is malformed!", hash),
+        }
+    }
+
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or_default()
+    }
+
+    pub fn is_ancestor(root_path: &path::Path, ancestor: &str, descendant: &str) -> bool {
+        let mut visited: HashSet<String> = HashSet::new();
+        let mut pending = vec![descendant.to_owned()];
+
+        while let Some(hash) = pending.pop() {
+            if hash == ancestor {
+                return true;
+            }
+
+            if !visited.insert(hash.clone()) {
+                continue;
+            }
+
+            pending.extend(Commit::read(root_path, &hash).parents);
+        }
+
+        false
+    }
+
+    /// Finds the best common ancestor of `first` and `second`: one reachable
+    /// from both that isn't an ancestor of another such commit. Criss-cross
+    /// histories can have several, then the most recent one is used.
+    pub fn merge_base(root_path: &path::Path, first: &str, second: &str) -> Option<String> {
+        let mut first_ancestors: HashSet<String> = HashSet::new();
+        let mut pending = vec![first.to_owned()];
+
+        while let Some(hash) = pending.pop() {
+            if first_ancestors.insert(hash.clone()) {
+                pending.extend(Commit::read(root_path, &hash).parents);
+            }
+        }
+
+        // Walking back from `second`, the common commits met first are the
+        // candidates; everything behind them is common too and never better.
+        let mut candidates: Vec<String> = Vec::new();
+        let mut visited: HashSet<String> = HashSet::new();
+        let mut pending: VecDeque<String> = VecDeque::from([second.to_owned()]);
+
+        while let Some(hash) = pending.pop_front() {
+            if !visited.insert(hash.clone()) {
+                continue;
+            }
+
+            match first_ancestors.contains(&hash) {
+                true => candidates.push(hash),
+                false => pending.extend(Commit::read(root_path, &hash).parents),
+            }
+        }
+
+        let best: Vec<&String> = candidates
+            .iter()
+            .filter(|candidate| {
+                !candidates.iter().any(|other| {
+                    other != *candidate && Commit::is_ancestor(root_path, candidate, other)
+                })
+            })
+            .collect();
+
+        best.into_iter()
+            .max_by_key(|hash| {
+                (
+                    Commit::read(root_path, hash).committer.timestamp,
+                    hash.to_owned(),
+                )
+            })
+            .cloned()
+    }
+}
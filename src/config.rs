@@ -1,11 +1,25 @@
-use std::{
-    fmt,
-    fs::{self, OpenOptions},
-    io::{self, Write},
-    path,
+use std::{env, fmt, fs, path, process, sync::OnceLock};
+
+use crate::{
+    constants::{CONFIG_FILE_PATH, GLOBAL_CONFIG_FILE_NAME, SYSTEM_CONFIG_FILE_PATH},
+    lockfile::LockFile,
 };
 
-use crate::constants::CONFIG_FILE_PATH;
+const ENV_CONFIG_PREFIX: &str = "KVC_CONFIG_";
+const DEFAULT_BASE_BRANCH: &str = "master";
+const DEFAULT_DIFF_CONTEXT: &str = "3";
+
+/// Every key kvc reads from its config files.
+pub const CONFIG_KEYS: [&str; 5] = [
+    "base_branch",
+    "core.threads",
+    "diff.context",
+    "user.email",
+    "user.name",
+];
+
+/// `-c key=value` pairs given before the command, the last config layer.
+static COMMAND_LINE_OVERRIDES: OnceLock<Vec<(String, String)>> = OnceLock::new();
 
 pub struct User {
     pub email: String,
@@ -15,32 +29,58 @@ pub struct User {
 pub struct Config {
     pub user: User,
     pub base_branch: String,
+    pub diff_context: String,
+    pub core_threads: String,
 }
 
 impl fmt::Display for Config {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "base_branch={}\nuser.email={}\nuser.name={}",
-            self.base_branch, self.user.email, self.user.name,
+            "base_branch={}\ncore.threads={}\ndiff.context={}\nuser.email={}\nuser.name={}",
+            self.base_branch, self.core_threads, self.diff_context, self.user.email, self.user.name,
         )
     }
 }
 
-impl Config {
-    pub fn clone(&self) -> Config {
-        Config {
-            base_branch: self.base_branch.clone(),
-            user: User {
-                name: self.user.name.clone(),
-                email: self.user.email.clone(),
-            },
+/// The config files, from the lowest priority to the highest. `KVC_CONFIG_*`
+/// variables and `-c key=value` go on top of them.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ConfigScope {
+    System,
+    Global,
+    Local,
+}
+
+/// A `key=value` set in one of the layers, with where it was read from.
+pub struct ConfigEntry {
+    pub origin: String,
+    pub key: String,
+    pub value: String,
+}
+
+impl ConfigScope {
+    /// The file behind a layer: `/etc/kvcconfig`, `~/.kvcconfig` or the
+    /// repository's `.kvc/config` (none outside a repository).
+    pub fn file_path(&self, root_path: &path::Path) -> Option<path::PathBuf> {
+        match self {
+            ConfigScope::System => Some(path::PathBuf::from(SYSTEM_CONFIG_FILE_PATH)),
+            ConfigScope::Global => {
+                env::var_os("HOME").map(|v| path::PathBuf::from(v).join(GLOBAL_CONFIG_FILE_NAME))
+            }
+            ConfigScope::Local => {
+                (!root_path.as_os_str().is_empty()).then(|| root_path.join(CONFIG_FILE_PATH))
+            }
         }
     }
+}
 
+impl Config {
     pub fn new() -> Config {
         Config {
             base_branch: String::from(""),
+            diff_context: String::from(""),
+            core_threads: String::from(""),
             user: User {
                 name: String::from(""),
                 email: String::from(""),
@@ -48,61 +88,138 @@ impl Config {
         }
     }
 
-    pub fn read_from_file() -> Result<Config, io::Error> {
-        let config_file_path = path::PathBuf::from(CONFIG_FILE_PATH);
-        let config_content = fs::read_to_string(&config_file_path).unwrap_or_default();
+    /// Reads the config of the repository at `root_path` (empty outside of
+    /// one) with every layer applied over the ones before it, starting from
+    /// the built-in defaults.
+    pub fn load(root_path: &path::Path) -> Config {
+        let mut config = Self::new();
+        config.base_branch = DEFAULT_BASE_BRANCH.to_owned();
+        config.diff_context = DEFAULT_DIFF_CONTEXT.to_owned();
 
-        if config_content == *"" {
-            println!("Your config file is empty!");
-            std::process::exit(1);
+        for entry in Self::entries(root_path) {
+            config.set(&entry.key, entry.value);
         }
 
-        let mut config = Self::new();
+        config
+    }
+
+    /// Every value set in the system, global and repository files, the
+    /// `KVC_CONFIG_*` variables and `-c`, lowest priority first. Empty values
+    /// count as unset, so the blank `user.name=` written by older versions of
+    /// `kvc init` doesn't hide the global one.
+    pub fn entries(root_path: &path::Path) -> Vec<ConfigEntry> {
+        let mut entries = Vec::new();
 
-        for line in config_content.split("\n") {
-            let (key, value) = Self::get_key_value(line);
+        for scope in [ConfigScope::System, ConfigScope::Global, ConfigScope::Local] {
+            entries.extend(Self::read_scope(scope, root_path));
+        }
 
-            match key {
-                "base_branch" => config.base_branch = value.to_owned(),
-                "user.name" => config.user.name = value.to_owned(),
-                "user.email" => config.user.email = value.to_owned(),
-                _ => continue,
+        for key in CONFIG_KEYS {
+            let var_name = Self::env_var_name(key);
+            if let Ok(value) = env::var(&var_name) {
+                entries.push(ConfigEntry {
+                    origin: format!("env:{}", var_name),
+                    key: key.to_owned(),
+                    value,
+                });
             }
         }
 
-        Ok(config)
+        for (key, value) in COMMAND_LINE_OVERRIDES.get().into_iter().flatten() {
+            entries.push(ConfigEntry {
+                origin: String::from("command line:"),
+                key: key.clone(),
+                value: value.clone(),
+            });
+        }
+
+        entries.retain(|v| !v.value.is_empty());
+
+        entries
     }
 
-    fn get_key_value(str: &str) -> (&str, &str) {
-        let mut splitted = str.split("=");
+    /// The entries of the file behind `scope`, none if it doesn't exist.
+    pub fn read_scope(scope: ConfigScope, root_path: &path::Path) -> Vec<ConfigEntry> {
+        let file_path = match scope.file_path(root_path) {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+
+        let origin = match scope {
+            ConfigScope::Local => format!("file:{}", CONFIG_FILE_PATH),
+            _ => format!("file:{}", file_path.display()),
+        };
+
+        let config_content = fs::read_to_string(&file_path).unwrap_or_default();
+
+        config_content
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| ConfigEntry {
+                origin: origin.clone(),
+                key: key.to_owned(),
+                value: value.to_owned(),
+            })
+            .collect()
+    }
 
-        let key = splitted.next().expect("No key was found!");
-        let value = splitted.next().expect("No value was found!");
+    /// `user.name` is read from `KVC_CONFIG_USER_NAME`, and so on.
+    pub fn env_var_name(key: &str) -> String {
+        format!(
+            "{}{}",
+            ENV_CONFIG_PREFIX,
+            key.to_uppercase().replace('.', "_")
+        )
+    }
 
-        (key, value)
+    pub fn set_command_line_overrides(overrides: Vec<(String, String)>) {
+        let _ = COMMAND_LINE_OVERRIDES.set(overrides);
     }
 
-    pub fn write_to_file(config: Config) -> Result<(), io::Error> {
-        let config_file_path = path::PathBuf::from(CONFIG_FILE_PATH);
+    fn set(&mut self, key: &str, value: String) {
+        match key {
+            "base_branch" => self.base_branch = value,
+            "diff.context" => self.diff_context = value,
+            "core.threads" => self.core_threads = value,
+            "user.name" => self.user.name = value,
+            "user.email" => self.user.email = value,
+            _ => (),
+        }
+    }
 
-        let mut file = match OpenOptions::new().write(true).open(&config_file_path) {
-            Ok(file) => file,
-            Err(_) => {
-                println!("Error opening config file!");
-                std::process::exit(1);
+    /// Sets `key` in the file behind `scope`, creating it if needed and
+    /// keeping the rest of its lines as they are.
+    pub fn write_value(scope: ConfigScope, root_path: &path::Path, key: &str, value: &str) {
+        let config_file_path = match scope.file_path(root_path) {
+            Some(v) => v,
+            None => {
+                println!("There is no config file to write {} to!", key);
+                process::exit(1);
             }
         };
 
-        let file_content = &config.to_string();
+        let mut lock = LockFile::acquire(&config_file_path);
+        let config_content = fs::read_to_string(&config_file_path).unwrap_or_default();
 
-        match file.write_all(file_content.as_bytes()) {
-            Ok(_) => (),
-            Err(e) => {
-                println!("Something went wrong writing to config file! {}", e);
-                std::process::exit(1);
+        let mut lines: Vec<String> = Vec::new();
+        let mut is_replaced = false;
+
+        for line in config_content.lines() {
+            match line.split_once('=') {
+                Some((line_key, _)) if line_key == key && is_replaced => continue,
+                Some((line_key, _)) if line_key == key => {
+                    lines.push(format!("{}={}", key, value));
+                    is_replaced = true;
+                }
+                _ => lines.push(line.to_owned()),
             }
         }
 
-        Ok(())
+        if !is_replaced {
+            lines.push(format!("{}={}", key, value));
+        }
+
+        lock.write(lines.join("\n").as_bytes());
+        lock.commit();
     }
 }
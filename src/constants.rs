@@ -1,5 +1,17 @@
 pub const ROOT_FOLDER_NAME: &str = ".kvc";
 pub const OBJECTS_FOLDER_NAME: &str = ".kvc/objects";
+pub const PACK_FOLDER_NAME: &str = ".kvc/objects/pack";
+pub const PACK_FILE_PREFIX: &str = "objects-";
+pub const PACK_FILE_EXTENSION: &str = "pack";
+pub const PACK_INDEX_FILE_NAME: &str = "objects.idx";
+pub const REFS_FOLDER_NAME: &str = ".kvc/refs";
+pub const LOGS_FOLDER_NAME: &str = ".kvc/logs";
+pub const HEAD_FILE_PATH: &str = ".kvc/HEAD";
 pub const INDEX_FILE_PATH: &str = ".kvc/index";
+pub const COMMIT_MESSAGE_FILE_PATH: &str = ".kvc/COMMIT_EDITMSG";
+pub const MERGE_HEAD_FILE_PATH: &str = ".kvc/MERGE_HEAD";
+pub const MERGE_MESSAGE_FILE_PATH: &str = ".kvc/MERGE_MSG";
 pub const CONFIG_FILE_PATH: &str = ".kvc/config";
+pub const GLOBAL_CONFIG_FILE_NAME: &str = ".kvcconfig";
+pub const SYSTEM_CONFIG_FILE_PATH: &str = "/etc/kvcconfig";
 pub const MINIMUN_LARGE_FILE_SIZE: u64 = 400;
@@ -0,0 +1,145 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_MINUTE: i64 = 60;
+const SECONDS_PER_HOUR: i64 = 60 * SECONDS_PER_MINUTE;
+const SECONDS_PER_DAY: i64 = 24 * SECONDS_PER_HOUR;
+const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+pub fn now() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(v) => v.as_secs(),
+        Err(_e) => 0,
+    }
+}
+
+pub fn format_timestamp(timestamp: u64, timezone: &str) -> String {
+    let local = timestamp as i64 + timezone_offset(timezone);
+    let days = local.div_euclid(SECONDS_PER_DAY);
+    let seconds_of_day = local.rem_euclid(SECONDS_PER_DAY);
+
+    let (year, month, day) = civil_from_days(days);
+    let weekday = WEEKDAYS[days.rem_euclid(7) as usize];
+
+    format!(
+        "{} {} {} {:02}:{:02}:{:02} {} {}",
+        weekday,
+        MONTHS[(month - 1) as usize],
+        day,
+        seconds_of_day / SECONDS_PER_HOUR,
+        seconds_of_day % SECONDS_PER_HOUR / SECONDS_PER_MINUTE,
+        seconds_of_day % SECONDS_PER_MINUTE,
+        year,
+        timezone
+    )
+}
+
+pub fn parse_date(s: &str) -> Option<u64> {
+    let s = s.trim();
+
+    if let Ok(v) = s.parse::<u64>() {
+        return Some(v);
+    }
+
+    if let Some(v) = parse_relative_date(s) {
+        return Some(v);
+    }
+
+    let (date, time) = match s.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (s, None),
+    };
+
+    let mut date_parts = date.split('-').map(|v| v.parse::<i64>().ok());
+    let year = date_parts.next()??;
+    let month = date_parts.next()??;
+    let day = date_parts.next()??;
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || date_parts.next().is_some() {
+        return None;
+    }
+
+    let mut seconds = days_from_civil(year, month, day) * SECONDS_PER_DAY;
+
+    if let Some(time) = time {
+        let mut time_parts = time.split(':').map(|v| v.parse::<i64>().ok());
+        seconds += time_parts.next()?? * SECONDS_PER_HOUR;
+        seconds += time_parts.next()?? * SECONDS_PER_MINUTE;
+        seconds += time_parts.next().unwrap_or(Some(0))?;
+    }
+
+    u64::try_from(seconds).ok()
+}
+
+fn parse_relative_date(s: &str) -> Option<u64> {
+    let normalized = s.replace('.', " ");
+    let mut words = normalized.split_whitespace();
+
+    let amount = words.next()?.parse::<i64>().ok()?;
+    let unit = words.next()?.trim_end_matches('s');
+
+    if words.next()? != "ago" {
+        return None;
+    }
+
+    let unit_seconds = match unit {
+        "second" => 1,
+        "minute" => SECONDS_PER_MINUTE,
+        "hour" => SECONDS_PER_HOUR,
+        "day" => SECONDS_PER_DAY,
+        "week" => 7 * SECONDS_PER_DAY,
+        _ => return None,
+    };
+
+    Some((now() as i64 - amount * unit_seconds).max(0) as u64)
+}
+
+fn timezone_offset(timezone: &str) -> i64 {
+    let (sign, digits) = match timezone.split_at_checked(1) {
+        Some(("-", digits)) => (-1, digits),
+        Some(("+", digits)) => (1, digits),
+        _ => return 0,
+    };
+
+    let hours = digits.get(..2).and_then(|v| v.parse::<i64>().ok());
+    let minutes = digits.get(2..4).and_then(|v| v.parse::<i64>().ok());
+
+    match (hours, minutes) {
+        (Some(hours), Some(minutes)) => {
+            sign * (hours * SECONDS_PER_HOUR + minutes * SECONDS_PER_MINUTE)
+        }
+        _ => 0,
+    }
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
@@ -0,0 +1,361 @@
+const BINARY_CHECK_SIZE: usize = 8000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+pub fn split_lines(content: &[u8]) -> Vec<&[u8]> {
+    content.split_inclusive(|v| *v == b'\n').collect()
+}
+
+pub fn is_binary(content: &[u8]) -> bool {
+    content[..content.len().min(BINARY_CHECK_SIZE)].contains(&0)
+}
+
+/// Computes the shortest edit script turning `old` into `new` with the Myers
+/// algorithm. Indexes in the returned edits point into `old` and `new`.
+pub fn myers_diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|idx| Edit::Equal(idx, idx)).collect();
+
+    edits.extend(
+        shortest_edit(old_middle, new_middle)
+            .into_iter()
+            .map(|edit| match edit {
+                Edit::Equal(x, y) => Edit::Equal(x + prefix, y + prefix),
+                Edit::Delete(x) => Edit::Delete(x + prefix),
+                Edit::Insert(y) => Edit::Insert(y + prefix),
+            }),
+    );
+
+    let old_suffix_start = old.len() - suffix;
+    let new_suffix_start = new.len() - suffix;
+    edits
+        .extend((0..suffix).map(|idx| Edit::Equal(old_suffix_start + idx, new_suffix_start + idx)));
+
+    edits
+}
+
+fn shortest_edit<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let old_len = old.len() as isize;
+    let new_len = new.len() as isize;
+    let max = old_len + new_len;
+
+    if max == 0 {
+        return Vec::new();
+    }
+
+    let offset = max + 1;
+    let mut furthest = vec![0isize; 2 * max as usize + 3];
+    // Backtracking at `depth` only looks at diagonals -depth - 1..=depth + 1,
+    // so that window is all that's kept of each step instead of the full
+    // vector, which would make memory grow with depth * (N + M).
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for depth in 0..=max {
+        trace
+            .push(furthest[(offset - depth - 1) as usize..=(offset + depth + 1) as usize].to_vec());
+
+        for diagonal in (-depth..=depth).step_by(2) {
+            let mut x = match diagonal == -depth
+                || (diagonal != depth
+                    && furthest[(diagonal - 1 + offset) as usize]
+                        < furthest[(diagonal + 1 + offset) as usize])
+            {
+                true => furthest[(diagonal + 1 + offset) as usize],
+                false => furthest[(diagonal - 1 + offset) as usize] + 1,
+            };
+            let mut y = x - diagonal;
+
+            while x < old_len && y < new_len && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            furthest[(diagonal + offset) as usize] = x;
+
+            if x >= old_len && y >= new_len {
+                break 'search;
+            }
+        }
+    }
+
+    let mut edits = Vec::new();
+    let mut x = old_len;
+    let mut y = new_len;
+
+    for (depth, window) in trace.iter().enumerate().rev() {
+        let depth = depth as isize;
+        let diagonal = x - y;
+        let window_offset = depth + 1;
+
+        let previous_diagonal = match diagonal == -depth
+            || (diagonal != depth
+                && window[(diagonal - 1 + window_offset) as usize]
+                    < window[(diagonal + 1 + window_offset) as usize])
+        {
+            true => diagonal + 1,
+            false => diagonal - 1,
+        };
+
+        let previous_x = window[(previous_diagonal + window_offset) as usize];
+        let previous_y = previous_x - previous_diagonal;
+
+        while x > previous_x && y > previous_y {
+            edits.push(Edit::Equal((x - 1) as usize, (y - 1) as usize));
+            x -= 1;
+            y -= 1;
+        }
+
+        if depth > 0 {
+            match x == previous_x {
+                true => edits.push(Edit::Insert((y - 1) as usize)),
+                false => edits.push(Edit::Delete((x - 1) as usize)),
+            }
+        }
+
+        x = previous_x;
+        y = previous_y;
+    }
+
+    edits.reverse();
+
+    edits
+}
+
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub edits: Vec<Edit>,
+}
+
+/// Groups the changes of `edits` into hunks keeping `context` unchanged lines
+/// around them. Hunks whose context would overlap are joined together.
+pub fn build_hunks(edits: &[Edit], context: usize) -> Vec<Hunk> {
+    let changes: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_idx, edit)| !matches!(edit, Edit::Equal(_, _)))
+        .map(|(idx, _edit)| idx)
+        .collect();
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+
+    for idx in changes {
+        let start = idx.saturating_sub(context);
+        let end = (idx + context + 1).min(edits.len());
+
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let hunk_edits = edits[start..end].to_vec();
+
+            // Positions of the first line of the hunk on each side.
+            let (old_start, new_start) =
+                edits[..start]
+                    .iter()
+                    .fold((0, 0), |(old, new), edit| match edit {
+                        Edit::Equal(_, _) => (old + 1, new + 1),
+                        Edit::Delete(_) => (old + 1, new),
+                        Edit::Insert(_) => (old, new + 1),
+                    });
+
+            let old_len = hunk_edits
+                .iter()
+                .filter(|v| !matches!(v, Edit::Insert(_)))
+                .count();
+            let new_len = hunk_edits
+                .iter()
+                .filter(|v| !matches!(v, Edit::Delete(_)))
+                .count();
+
+            Hunk {
+                old_start,
+                old_len,
+                new_start,
+                new_len,
+                edits: hunk_edits,
+            }
+        })
+        .collect()
+}
+
+pub fn count_changes(edits: &[Edit]) -> (usize, usize) {
+    edits
+        .iter()
+        .fold((0, 0), |(insertions, deletions), edit| match edit {
+            Edit::Insert(_) => (insertions + 1, deletions),
+            Edit::Delete(_) => (insertions, deletions + 1),
+            Edit::Equal(_, _) => (insertions, deletions),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rebuilds `new` from `old` following `edits`, checking that every
+    /// line of both sides is visited once and in order.
+    fn apply<T: Clone + PartialEq + std::fmt::Debug>(
+        old: &[T],
+        new: &[T],
+        edits: &[Edit],
+    ) -> Vec<T> {
+        let (mut old_idx, mut new_idx) = (0, 0);
+        let mut result = Vec::new();
+
+        for edit in edits {
+            match *edit {
+                Edit::Equal(x, y) => {
+                    assert_eq!((x, y), (old_idx, new_idx));
+                    assert_eq!(old[x], new[y]);
+                    result.push(old[x].clone());
+                    old_idx += 1;
+                    new_idx += 1;
+                }
+                Edit::Delete(x) => {
+                    assert_eq!(x, old_idx);
+                    old_idx += 1;
+                }
+                Edit::Insert(y) => {
+                    assert_eq!(y, new_idx);
+                    result.push(new[y].clone());
+                    new_idx += 1;
+                }
+            }
+        }
+
+        assert_eq!((old_idx, new_idx), (old.len(), new.len()));
+        result
+    }
+
+    #[test]
+    fn myers_diff_finds_the_shortest_edit_script() {
+        let old: Vec<char> = "ABCABBA".chars().collect();
+        let new: Vec<char> = "CBABAC".chars().collect();
+
+        let edits = myers_diff(&old, &new);
+
+        assert_eq!(apply(&old, &new, &edits), new);
+        assert_eq!(count_changes(&edits), (2, 3));
+    }
+
+    #[test]
+    fn myers_diff_keeps_common_prefix_and_suffix() {
+        let old = ["a", "b", "c", "d", "e"];
+        let new = ["a", "b", "x", "d", "e"];
+
+        let edits = myers_diff(&old, &new);
+
+        assert_eq!(
+            edits,
+            vec![
+                Edit::Equal(0, 0),
+                Edit::Equal(1, 1),
+                Edit::Delete(2),
+                Edit::Insert(2),
+                Edit::Equal(3, 3),
+                Edit::Equal(4, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn myers_diff_handles_empty_sides() {
+        let lines = ["a", "b"];
+
+        assert!(myers_diff::<&str>(&[], &[]).is_empty());
+        assert_eq!(
+            myers_diff(&[], &lines),
+            vec![Edit::Insert(0), Edit::Insert(1)]
+        );
+        assert_eq!(
+            myers_diff(&lines, &[]),
+            vec![Edit::Delete(0), Edit::Delete(1)]
+        );
+    }
+
+    #[test]
+    fn myers_diff_rebuilds_the_new_side() {
+        let old = split_lines(b"one\ntwo\nthree\nfour\nfive\nsix\n");
+        let new = split_lines(b"zero\none\nthree\nfour\n4.5\nfive\nsix");
+
+        let edits = myers_diff(&old, &new);
+
+        assert_eq!(apply(&old, &new, &edits), new);
+        assert_eq!(count_changes(&edits), (3, 2));
+    }
+
+    #[test]
+    fn build_hunks_joins_changes_with_overlapping_context() {
+        let old: Vec<usize> = (0..20).collect();
+        let mut new = old.clone();
+        new[5] = 100;
+        new[10] = 101;
+
+        let hunks = build_hunks(&myers_diff(&old, &new), 3);
+
+        assert_eq!(hunks.len(), 1);
+        assert_eq!((hunks[0].old_start, hunks[0].old_len), (2, 12));
+        assert_eq!((hunks[0].new_start, hunks[0].new_len), (2, 12));
+    }
+
+    #[test]
+    fn build_hunks_splits_distant_changes() {
+        let old: Vec<usize> = (0..30).collect();
+        let mut new = old.clone();
+        new[2] = 100;
+        new.remove(20);
+
+        let hunks = build_hunks(&myers_diff(&old, &new), 3);
+
+        assert_eq!(hunks.len(), 2);
+        assert_eq!((hunks[0].old_start, hunks[0].old_len), (0, 6));
+        assert_eq!((hunks[0].new_start, hunks[0].new_len), (0, 6));
+        assert_eq!((hunks[1].old_start, hunks[1].old_len), (17, 7));
+        assert_eq!((hunks[1].new_start, hunks[1].new_len), (17, 6));
+    }
+
+    #[test]
+    fn build_hunks_joins_hunks_whose_context_touches() {
+        let old: Vec<usize> = (0..10).collect();
+
+        let mut touching = old.clone();
+        touching.remove(5);
+        touching.remove(2);
+        assert_eq!(build_hunks(&myers_diff(&old, &touching), 1).len(), 1);
+
+        let mut apart = old.clone();
+        apart.remove(6);
+        apart.remove(2);
+        assert_eq!(build_hunks(&myers_diff(&old, &apart), 1).len(), 2);
+    }
+
+    #[test]
+    fn build_hunks_is_empty_without_changes() {
+        let lines = ["a", "b", "c"];
+
+        assert!(build_hunks(&myers_diff(&lines, &lines), 3).is_empty());
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    collections::HashMap,
+    fs, path,
+    sync::{Arc, Mutex},
+};
+
+use crate::{constants::ROOT_FOLDER_NAME, utils::glob_match};
+
+pub const IGNORE_FILE_NAME: &str = ".kvcignore";
+
+#[derive(Clone)]
+pub struct IgnoreRule {
+    pub source: String,
+    pub line: usize,
+    pub pattern: String,
+    base: String,
+    segments: Vec<String>,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+/// The `.kvcignore` rules of the working tree. Each directory's file is only
+/// read the first time a path below it is looked up, so callers touching a
+/// few paths don't pay for a walk of the whole tree.
+pub struct IgnoreRules {
+    root_path: path::PathBuf,
+    loaded: Mutex<HashMap<String, Arc<Vec<IgnoreRule>>>>,
+}
+
+impl IgnoreRule {
+    fn parse(line: &str, base: &str, source: &str, number: usize) -> Option<IgnoreRule> {
+        let trimmed = match line.ends_with("\\ ") {
+            true => line,
+            false => line.trim_end(),
+        };
+
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            return None;
+        }
+
+        let (negated, pattern) = match trimmed.strip_prefix('!') {
+            Some(v) => (true, v),
+            None => (false, trimmed),
+        };
+
+        let pattern = match pattern.strip_prefix('\\') {
+            Some(v) if v.starts_with('!') || v.starts_with('#') => v,
+            _ => pattern,
+        };
+
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(v) => (true, v),
+            None => (false, pattern),
+        };
+
+        let anchored = pattern.contains('/');
+        let segments = pattern
+            .trim_start_matches('/')
+            .split('/')
+            .map(|v| v.replace("\\ ", " "))
+            .collect();
+
+        Some(IgnoreRule {
+            source: source.to_owned(),
+            line: number,
+            pattern: trimmed.to_owned(),
+            base: base.to_owned(),
+            segments,
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+
+    fn matches(&self, file_path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        let relative_path = match self.base.is_empty() {
+            true => file_path,
+            false => match file_path.strip_prefix(&format!("{}/", self.base)) {
+                Some(v) => v,
+                None => return false,
+            },
+        };
+
+        let path_segments: Vec<&str> = relative_path.split('/').collect();
+
+        if !self.anchored {
+            let name = path_segments.last().copied().unwrap_or_default();
+            return glob_match(&self.segments[0], name);
+        }
+
+        let pattern_segments: Vec<&str> = self.segments.iter().map(|v| &v[..]).collect();
+
+        match_segments(&pattern_segments, &path_segments)
+    }
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|idx| match_segments(rest, &path[idx..])),
+        Some((first, rest)) => match path.split_first() {
+            Some((name, path_rest)) => glob_match(first, name) && match_segments(rest, path_rest),
+            None => false,
+        },
+    }
+}
+
+impl IgnoreRules {
+    pub fn load(root_path: &path::Path) -> IgnoreRules {
+        IgnoreRules {
+            root_path: root_path.to_path_buf(),
+            loaded: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn dir_rules(&self, relative_dir: &str) -> Arc<Vec<IgnoreRule>> {
+        let mut loaded = match self.loaded.lock() {
+            Ok(v) => v,
+            Err(e) => e.into_inner(),
+        };
+
+        if let Some(rules) = loaded.get(relative_dir) {
+            return rules.clone();
+        }
+
+        let source = match relative_dir.is_empty() {
+            true => IGNORE_FILE_NAME.to_owned(),
+            false => format!("{}/{}", relative_dir, IGNORE_FILE_NAME),
+        };
+
+        let rules: Vec<IgnoreRule> = match fs::read_to_string(self.root_path.join(&source)) {
+            Ok(content) => content
+                .lines()
+                .enumerate()
+                .filter_map(|(idx, line)| IgnoreRule::parse(line, relative_dir, &source, idx + 1))
+                .collect(),
+            Err(_e) => Vec::new(),
+        };
+
+        let rules = Arc::new(rules);
+        loaded.insert(relative_dir.to_owned(), rules.clone());
+
+        rules
+    }
+
+    /// Returns the rule deciding whether `file_path` (relative to the root) is
+    /// ignored, looking at its parent directories first since nothing inside
+    /// an ignored directory can be re-included.
+    pub fn matching_rule(&self, file_path: &str, is_dir: bool) -> Option<IgnoreRule> {
+        let segments: Vec<&str> = file_path.split('/').collect();
+
+        for idx in 1..segments.len() {
+            let parent = segments[..idx].join("/");
+
+            if let Some(rule) = self.last_match(&parent, true) {
+                if !rule.negated {
+                    return Some(rule);
+                }
+            }
+        }
+
+        self.last_match(file_path, is_dir)
+    }
+
+    pub fn is_ignored(&self, file_path: &str, is_dir: bool) -> bool {
+        if file_path.split('/').next() == Some(ROOT_FOLDER_NAME) {
+            return true;
+        }
+
+        self.matching_rule(file_path, is_dir)
+            .is_some_and(|rule| !rule.negated)
+    }
+
+    /// Whether a walk should skip `file_path`: it is ignored and nothing at or
+    /// below it is tracked in `index`, since tracked files are never ignored.
+    pub fn excludes(&self, file_path: &str, is_dir: bool, index: &HashMap<String, String>) -> bool {
+        if !self.is_ignored(file_path, is_dir) {
+            return false;
+        }
+
+        let dir_prefix = format!("{}/", file_path);
+
+        !index
+            .keys()
+            .any(|v| v == file_path || (is_dir && v.starts_with(&dir_prefix)))
+    }
+
+    /// Rules from deeper directories win over the ones from their parents,
+    /// and later lines over earlier ones.
+    fn last_match(&self, file_path: &str, is_dir: bool) -> Option<IgnoreRule> {
+        let segments: Vec<&str> = file_path.split('/').collect();
+
+        (0..segments.len()).rev().find_map(|depth| {
+            self.dir_rules(&segments[..depth].join("/"))
+                .iter()
+                .rev()
+                .find(|rule| rule.matches(file_path, is_dir))
+                .cloned()
+        })
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path, process,
+};
+
+const LOCK_EXTENSION: &str = "lock";
+
+/// Exclusive `<file>.lock` next to a file being rewritten. The new content is
+/// written to the lock, synced and renamed over the file, so readers see the
+/// old or the new version and never a partial one. Dropping a lock without
+/// committing it leaves the file untouched.
+pub struct LockFile {
+    target: path::PathBuf,
+    lock_path: path::PathBuf,
+    file: fs::File,
+    committed: bool,
+}
+
+impl LockFile {
+    /// Takes the lock of `path`, exiting when another kvc process holds it.
+    pub fn acquire(path: &path::Path) -> LockFile {
+        match Self::try_acquire(path) {
+            Some(v) => v,
+            None => {
+                let lock_path = Self::lock_path(path);
+                let held_msg = format!("Unable to create '{}': File exists.", lock_path.display())
+                    + "\n\n"
+                    + "Another kvc process seems to be running in this repository."
+                    + " Wait for it to finish and try again."
+                    + "\n"
+                    + "If no other kvc process is running, one may have crashed:"
+                    + " remove the file manually to continue.";
+
+                println!("{}", held_msg);
+                process::exit(1);
+            }
+        }
+    }
+
+    /// Takes the lock of `path`, or returns `None` if it is already held.
+    pub fn try_acquire(path: &path::Path) -> Option<LockFile> {
+        let lock_path = Self::lock_path(path);
+
+        if let Some(parent) = path.parent() {
+            match fs::create_dir_all(parent) {
+                Ok(v) => v,
+                Err(e) => panic!("Could not create {}: {}", parent.display(), e),
+            }
+        }
+
+        let file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+        {
+            Ok(v) => v,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return None,
+            Err(e) => panic!("Could not create {}: {}", lock_path.display(), e),
+        };
+
+        Some(LockFile {
+            target: path.to_path_buf(),
+            lock_path,
+            file,
+            committed: false,
+        })
+    }
+
+    pub fn is_lock_path(path: &path::Path) -> bool {
+        path.extension().is_some_and(|v| v == LOCK_EXTENSION)
+    }
+
+    fn lock_path(path: &path::Path) -> path::PathBuf {
+        path.with_file_name(format!(
+            "{}.{}",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            LOCK_EXTENSION
+        ))
+    }
+
+    pub fn write(&mut self, content: &[u8]) {
+        match self.file.write_all(content) {
+            Ok(v) => v,
+            Err(e) => panic!("Could not write {}: {}", self.lock_path.display(), e),
+        }
+    }
+
+    /// Syncs the new content to disk and moves it over the locked file.
+    pub fn commit(mut self) {
+        match self.file.sync_all() {
+            Ok(v) => v,
+            Err(e) => panic!("Could not sync {}: {}", self.lock_path.display(), e),
+        }
+
+        match fs::rename(&self.lock_path, &self.target) {
+            Ok(v) => v,
+            Err(e) => panic!("Could not update {}: {}", self.target.display(), e),
+        }
+
+        self.committed = true;
+    }
+
+    /// Removes the locked file itself, releasing the lock afterwards.
+    pub fn delete_target(self) -> io::Result<()> {
+        fs::remove_file(&self.target)
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.lock_path);
+        }
+    }
+}
@@ -2,14 +2,28 @@ use clap::Parser;
 use commands::Cli;
 
 mod commands;
+mod commit;
 mod config;
 mod constants;
+mod date;
+mod diff;
+mod ignore;
+mod lockfile;
+mod merge;
+mod object;
+mod parallel;
+mod reachability;
+mod refs;
 mod staging_area;
+mod tag;
 mod traits;
+mod tree;
 mod utils;
 
 fn main() {
-    let args = match Cli::try_parse() {
+    let (cli_args, config_overrides) = Cli::take_config_overrides(std::env::args().collect());
+
+    let args = match Cli::try_parse_from(cli_args) {
         Ok(res) => res,
         Err(..) => {
             println!("This command does not exist. Try kvc help.");
@@ -17,5 +31,5 @@ fn main() {
         }
     };
 
-    Cli::execute(args.command, args.args);
+    Cli::execute(args.command, args.args, config_overrides);
 }
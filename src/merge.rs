@@ -0,0 +1,234 @@
+use crate::diff::{myers_diff, split_lines, Edit};
+
+const OURS_MARKER: &[u8] = b"<<<<<<< ";
+const SEPARATOR_MARKER: &[u8] = b"=======\n";
+const THEIRS_MARKER: &[u8] = b">>>>>>> ";
+
+pub struct MergeResult {
+    pub content: Vec<u8>,
+    pub conflicts: usize,
+}
+
+/// Line-level three-way merge of `ours` and `theirs` against their common
+/// `base`. Hunks changed on both sides in different ways are written between
+/// conflict markers labelled with `ours_label` and `theirs_label`.
+pub fn merge_lines(
+    base: &[u8],
+    ours: &[u8],
+    theirs: &[u8],
+    ours_label: &str,
+    theirs_label: &str,
+) -> MergeResult {
+    let base_lines = split_lines(base);
+    let ours_lines = split_lines(ours);
+    let theirs_lines = split_lines(theirs);
+
+    let ours_matches = match_lines(&base_lines, &ours_lines);
+    let theirs_matches = match_lines(&base_lines, &theirs_lines);
+
+    let mut result = MergeResult {
+        content: Vec::new(),
+        conflicts: 0,
+    };
+
+    let (mut base_idx, mut ours_idx, mut theirs_idx) = (0, 0, 0);
+
+    loop {
+        if base_idx < base_lines.len()
+            && ours_matches[base_idx] == Some(ours_idx)
+            && theirs_matches[base_idx] == Some(theirs_idx)
+        {
+            result.content.extend_from_slice(base_lines[base_idx]);
+            base_idx += 1;
+            ours_idx += 1;
+            theirs_idx += 1;
+            continue;
+        }
+
+        // The next base line kept by both sides closes the current hunk.
+        let stable = (base_idx..base_lines.len()).find_map(|idx| {
+            match (ours_matches[idx], theirs_matches[idx]) {
+                (Some(ours_end), Some(theirs_end)) => Some((idx, ours_end, theirs_end)),
+                _ => None,
+            }
+        });
+
+        let (base_end, ours_end, theirs_end) =
+            stable.unwrap_or((base_lines.len(), ours_lines.len(), theirs_lines.len()));
+
+        let base_hunk = &base_lines[base_idx..base_end];
+        let ours_hunk = &ours_lines[ours_idx..ours_end];
+        let theirs_hunk = &theirs_lines[theirs_idx..theirs_end];
+
+        if ours_hunk == base_hunk || ours_hunk == theirs_hunk {
+            extend_lines(&mut result.content, theirs_hunk);
+        } else if theirs_hunk == base_hunk {
+            extend_lines(&mut result.content, ours_hunk);
+        } else {
+            write_conflict(
+                &mut result.content,
+                ours_hunk,
+                theirs_hunk,
+                ours_label,
+                theirs_label,
+            );
+            result.conflicts += 1;
+        }
+
+        if stable.is_none() {
+            break;
+        }
+
+        base_idx = base_end;
+        ours_idx = ours_end;
+        theirs_idx = theirs_end;
+    }
+
+    result
+}
+
+fn match_lines(base: &[&[u8]], other: &[&[u8]]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; base.len()];
+
+    for edit in myers_diff(base, other) {
+        if let Edit::Equal(base_idx, other_idx) = edit {
+            matches[base_idx] = Some(other_idx);
+        }
+    }
+
+    matches
+}
+
+fn extend_lines(content: &mut Vec<u8>, lines: &[&[u8]]) {
+    for line in lines {
+        content.extend_from_slice(line);
+    }
+}
+
+fn write_conflict(
+    content: &mut Vec<u8>,
+    ours: &[&[u8]],
+    theirs: &[&[u8]],
+    ours_label: &str,
+    theirs_label: &str,
+) {
+    content.extend_from_slice(OURS_MARKER);
+    content.extend_from_slice(format!("{}\n", ours_label).as_bytes());
+    extend_conflict_side(content, ours);
+
+    content.extend_from_slice(SEPARATOR_MARKER);
+    extend_conflict_side(content, theirs);
+
+    content.extend_from_slice(THEIRS_MARKER);
+    content.extend_from_slice(format!("{}\n", theirs_label).as_bytes());
+}
+
+fn extend_conflict_side(content: &mut Vec<u8>, lines: &[&[u8]]) {
+    extend_lines(content, lines);
+
+    // Keeps the markers on their own lines when the file has no final newline.
+    if lines.last().is_some_and(|v| !v.ends_with(b"\n")) {
+        content.push(b'\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(base: &str, ours: &str, theirs: &str) -> (String, usize) {
+        let result = merge_lines(
+            base.as_bytes(),
+            ours.as_bytes(),
+            theirs.as_bytes(),
+            "HEAD",
+            "feature",
+        );
+
+        (String::from_utf8(result.content).unwrap(), result.conflicts)
+    }
+
+    #[test]
+    fn merges_changes_to_different_lines() {
+        let base = "a\nb\nc\nd\ne\n";
+        let ours = "A\nb\nc\nd\ne\n";
+        let theirs = "a\nb\nc\nd\nE\n";
+
+        assert_eq!(merge(base, ours, theirs), ("A\nb\nc\nd\nE\n".to_owned(), 0));
+    }
+
+    #[test]
+    fn takes_the_same_change_from_both_sides_once() {
+        let base = "a\nb\nc\n";
+        let both = "a\nB\nc\n";
+
+        assert_eq!(merge(base, both, both), (both.to_owned(), 0));
+    }
+
+    #[test]
+    fn marks_conflicting_changes() {
+        let base = "a\nb\nc\n";
+        let ours = "a\nours\nc\n";
+        let theirs = "a\ntheirs\nc\n";
+
+        assert_eq!(
+            merge(base, ours, theirs),
+            (
+                "a\n<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> feature\nc\n".to_owned(),
+                1
+            )
+        );
+    }
+
+    #[test]
+    fn counts_each_conflicting_hunk() {
+        let base = "a\nb\nc\nd\ne\n";
+        let ours = "1\nb\nc\nd\n2\n";
+        let theirs = "3\nb\nc\nd\n4\n";
+
+        assert_eq!(merge(base, ours, theirs).1, 2);
+    }
+
+    #[test]
+    fn keeps_conflict_markers_on_their_own_lines() {
+        let (content, conflicts) = merge("a", "ours", "theirs");
+
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            content,
+            "<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> feature\n"
+        );
+    }
+
+    #[test]
+    fn applies_a_deletion_of_untouched_lines() {
+        let base = "a\nb\nc\nd\n";
+        let ours = "a\nc\nd\n";
+        let theirs = "a\nb\nc\nD\n";
+
+        assert_eq!(merge(base, ours, theirs), ("a\nc\nD\n".to_owned(), 0));
+    }
+
+    #[test]
+    fn marks_a_deletion_of_modified_lines() {
+        let base = "a\nb\nc\n";
+        let ours = "a\nc\n";
+        let theirs = "a\nB\nc\n";
+
+        assert_eq!(
+            merge(base, ours, theirs),
+            (
+                "a\n<<<<<<< HEAD\n=======\nB\n>>>>>>> feature\nc\n".to_owned(),
+                1
+            )
+        );
+    }
+
+    #[test]
+    fn marks_a_deleted_file_modified_on_the_other_side() {
+        let (content, conflicts) = merge("a\nb\n", "", "a\nB\n");
+
+        assert_eq!(conflicts, 1);
+        assert_eq!(content, "<<<<<<< HEAD\n=======\na\nB\n>>>>>>> feature\n");
+    }
+}
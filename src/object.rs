@@ -0,0 +1,567 @@
+use std::{
+    fmt, fs,
+    io::{BufReader, ErrorKind, Read, Write},
+    path, process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    constants::{MINIMUN_LARGE_FILE_SIZE, OBJECTS_FOLDER_NAME},
+    utils::{check_file_size, generate_hash, read_file, segment_hash},
+};
+
+mod delta;
+pub mod pack;
+
+const HEADER_TERMINATOR: u8 = b'\0';
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+const TEMP_OBJECT_PREFIX: &str = "tmp_obj_";
+const MIN_ABBREVIATED_HASH_SIZE: usize = 4;
+
+/// Tells apart the temporary files of objects written by different threads.
+static TEMP_OBJECT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ObjectKind {
+    Blob,
+    Tree,
+    Commit,
+    Tag,
+}
+
+impl fmt::Display for ObjectKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ObjectKind::Blob => write!(f, "blob"),
+            ObjectKind::Tree => write!(f, "tree"),
+            ObjectKind::Commit => write!(f, "commit"),
+            ObjectKind::Tag => write!(f, "tag"),
+        }
+    }
+}
+
+impl ObjectKind {
+    pub fn parse(s: &str) -> Option<ObjectKind> {
+        match s {
+            "blob" => Some(ObjectKind::Blob),
+            "tree" => Some(ObjectKind::Tree),
+            "commit" => Some(ObjectKind::Commit),
+            "tag" => Some(ObjectKind::Tag),
+            _ => None,
+        }
+    }
+}
+
+pub struct Object {
+    pub kind: ObjectKind,
+    pub content: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum ObjectError {
+    NotFound,
+    MissingHeader,
+    InvalidKind(String),
+    InvalidLength(String),
+    LengthMismatch {
+        expected: usize,
+        found: usize,
+    },
+    KindMismatch {
+        expected: ObjectKind,
+        found: ObjectKind,
+    },
+    ChangedWhileReading(String),
+    AmbiguousHash(String),
+    Corrupt(String),
+    UnsupportedPath(String),
+}
+
+impl fmt::Display for ObjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjectError::NotFound => write!(f, "object not found"),
+            ObjectError::MissingHeader => write!(f, "object header is missing"),
+            ObjectError::InvalidKind(v) => write!(f, "invalid object kind `{}`", v),
+            ObjectError::InvalidLength(v) => write!(f, "invalid object length `{}`", v),
+            ObjectError::LengthMismatch { expected, found } => write!(
+                f,
+                "object length mismatch, header says {} but found {}",
+                expected, found
+            ),
+            ObjectError::KindMismatch { expected, found } => {
+                write!(f, "expected a {} object but found a {}", expected, found)
+            }
+            ObjectError::ChangedWhileReading(v) => {
+                write!(f, "the file {} changed while it was being read", v)
+            }
+            ObjectError::AmbiguousHash(v) => write!(f, "the short hash {} is ambiguous", v),
+            ObjectError::Corrupt(v) => write!(f, "object data is corrupt: {}", v),
+            ObjectError::UnsupportedPath(v) => write!(
+                f,
+                "the path {:?} can't be stored, names with newlines or tabs are not supported",
+                v
+            ),
+        }
+    }
+}
+
+impl Object {
+    pub fn header(kind: ObjectKind, length: usize) -> Vec<u8> {
+        let mut header = format!("{} {}", kind, length).into_bytes();
+        header.push(HEADER_TERMINATOR);
+
+        header
+    }
+
+    pub fn serialize(kind: ObjectKind, content: &[u8]) -> Vec<u8> {
+        let mut serialized = Self::header(kind, content.len());
+        serialized.extend_from_slice(content);
+
+        serialized
+    }
+
+    pub fn parse(raw: &[u8]) -> Result<Object, ObjectError> {
+        let terminator_idx = match raw.iter().position(|v| *v == HEADER_TERMINATOR) {
+            Some(v) => v,
+            None => return Err(ObjectError::MissingHeader),
+        };
+
+        let header = match std::str::from_utf8(&raw[..terminator_idx]) {
+            Ok(v) => v,
+            Err(_e) => return Err(ObjectError::MissingHeader),
+        };
+        let content = &raw[terminator_idx + 1..];
+
+        let (kind, length) = match header.split_once(' ') {
+            Some(v) => v,
+            None => return Err(ObjectError::MissingHeader),
+        };
+
+        let kind = match ObjectKind::parse(kind) {
+            Some(v) => v,
+            None => return Err(ObjectError::InvalidKind(kind.to_owned())),
+        };
+
+        let length = match length.parse::<usize>() {
+            Ok(v) => v,
+            Err(_e) => return Err(ObjectError::InvalidLength(length.to_owned())),
+        };
+
+        if length != content.len() {
+            return Err(ObjectError::LengthMismatch {
+                expected: length,
+                found: content.len(),
+            });
+        }
+
+        Ok(Object {
+            kind,
+            content: content.to_vec(),
+        })
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.content).into_owned()
+    }
+}
+
+pub fn hash_object(kind: ObjectKind, content: &[u8]) -> String {
+    match generate_hash(&Object::serialize(kind, content)) {
+        Ok(v) => v,
+        Err(_e) => panic!("Could not generate object hash!"),
+    }
+}
+
+pub fn object_path(root_path: &path::Path, hash: &str) -> path::PathBuf {
+    let (prefix, suffix) = segment_hash(hash);
+
+    root_path
+        .join(OBJECTS_FOLDER_NAME)
+        .join(prefix)
+        .join(suffix)
+}
+
+pub fn create_prefix_dir(root_path: &path::Path, prefix: &str) {
+    let path = root_path.join(OBJECTS_FOLDER_NAME).join(prefix);
+
+    if fs::exists(&path).expect("something went wrong!") {
+        return;
+    }
+
+    // Another thread writing an object with the same prefix may win the race.
+    match fs::create_dir(&path) {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => (),
+        Err(e) => panic!("Error on create prefix dir: {}", e),
+    };
+}
+
+pub fn write_object(root_path: &path::Path, kind: ObjectKind, content: &[u8]) -> String {
+    let serialized = Object::serialize(kind, content);
+    let hash = match generate_hash(&serialized) {
+        Ok(v) => v,
+        Err(_e) => panic!("Could not generate object hash!"),
+    };
+
+    if object_exists(root_path, &hash) {
+        return hash;
+    }
+
+    // Written aside and renamed into place, so a crash never leaves a
+    // truncated object under its final name.
+    let (temp_path, temp_file) = compress_to_temp(root_path, &serialized);
+    move_object_into_place(root_path, &temp_path, temp_file, &hash);
+
+    hash
+}
+
+/// Copies a packed object out as a loose one dated like the pack, so it
+/// keeps aging towards expiry when the pack it was in is replaced.
+pub fn unpack_object(root_path: &path::Path, hash: &str) {
+    let object = match read_object(root_path, hash) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not read object {}: {}", hash, e),
+    };
+
+    let (temp_path, temp_file) =
+        compress_to_temp(root_path, &Object::serialize(object.kind, &object.content));
+
+    let packed_at = pack::pack_path(root_path).and_then(|v| fs::metadata(v).ok()?.modified().ok());
+    if let Some(packed_at) = packed_at {
+        match temp_file.set_modified(packed_at) {
+            Ok(v) => v,
+            Err(e) => panic!("Could not date unpacked object {}: {}", hash, e),
+        }
+    }
+
+    match temp_file.sync_all() {
+        Ok(v) => v,
+        Err(e) => panic!("Could not flush temporary object file! {}", e),
+    }
+
+    rename_object_into_place(root_path, &temp_path, hash);
+}
+
+fn compress_to_temp(root_path: &path::Path, serialized: &[u8]) -> (path::PathBuf, fs::File) {
+    let temp_path = temp_object_path(root_path);
+    let mut encoder = ZlibEncoder::new(create_temp_object(&temp_path), Compression::default());
+
+    match encoder.write_all(serialized) {
+        Ok(ok) => ok,
+        Err(e) => panic!("Error writing object file: {}", e),
+    };
+
+    match encoder.finish() {
+        Ok(v) => (temp_path, v),
+        Err(e) => panic!("Error compressing object file: {}", e),
+    }
+}
+
+pub fn hash_file(path: &str) -> Result<String, ObjectError> {
+    if check_file_size(path) <= MINIMUN_LARGE_FILE_SIZE {
+        return Ok(hash_object(ObjectKind::Blob, &read_file(path)));
+    }
+
+    stream_file_object(path, None)
+}
+
+/// Stores the file at `path` as a blob. Files changed while being streamed
+/// are not stored, so the caller can release its locks before reporting it.
+pub fn write_blob_from_file(root_path: &path::Path, path: &str) -> Result<String, ObjectError> {
+    if check_file_size(path) <= MINIMUN_LARGE_FILE_SIZE {
+        return Ok(write_object(root_path, ObjectKind::Blob, &read_file(path)));
+    }
+
+    let temp_path = temp_object_path(root_path);
+    let mut encoder = ZlibEncoder::new(create_temp_object(&temp_path), Compression::default());
+    let hash = match stream_file_object(path, Some(&mut encoder)) {
+        Ok(v) => v,
+        Err(e) => {
+            fs::remove_file(&temp_path).unwrap_or_default();
+            return Err(e);
+        }
+    };
+
+    let temp_file = match encoder.finish() {
+        Ok(v) => v,
+        Err(e) => panic!("Error compressing object file: {}", e),
+    };
+
+    move_object_into_place(root_path, &temp_path, temp_file, &hash);
+
+    Ok(hash)
+}
+
+fn stream_file_object(
+    path: &str,
+    mut output: Option<&mut dyn Write>,
+) -> Result<String, ObjectError> {
+    let file_input = match fs::File::open(path) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not open file {}: {}", path, e),
+    };
+    let file_size = check_file_size(path);
+
+    let header = Object::header(ObjectKind::Blob, file_size as usize);
+    let mut hasher = Sha256::new();
+    hasher.update(&header);
+
+    if let Some(writer) = output.as_mut() {
+        match writer.write_all(&header) {
+            Ok(v) => v,
+            Err(e) => panic!("Error writing object file: {}", e),
+        }
+    }
+
+    let mut reader = BufReader::new(file_input);
+    let mut buffer = vec![0_u8; STREAM_BUFFER_SIZE];
+    let mut total_read: u64 = 0;
+
+    loop {
+        let count = match reader.read(&mut buffer) {
+            Ok(v) => v,
+            Err(e) => panic!("Could not read file {}: {}", path, e),
+        };
+
+        if count == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..count]);
+
+        if let Some(writer) = output.as_mut() {
+            match writer.write_all(&buffer[..count]) {
+                Ok(v) => v,
+                Err(e) => panic!("Error writing object file: {}", e),
+            }
+        }
+
+        total_read += count as u64;
+    }
+
+    if total_read != file_size {
+        return Err(ObjectError::ChangedWhileReading(path.to_owned()));
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn create_temp_object(temp_path: &path::Path) -> fs::File {
+    match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(temp_path)
+    {
+        Ok(v) => v,
+        Err(e) => panic!("Could not create temporary object file! {}", e),
+    }
+}
+
+fn move_object_into_place(
+    root_path: &path::Path,
+    temp_path: &path::Path,
+    temp_file: fs::File,
+    hash: &str,
+) {
+    match temp_file.sync_all() {
+        Ok(v) => v,
+        Err(e) => panic!("Could not flush temporary object file! {}", e),
+    }
+
+    if object_exists(root_path, hash) {
+        fs::remove_file(temp_path).unwrap_or_default();
+        return;
+    }
+
+    rename_object_into_place(root_path, temp_path, hash);
+}
+
+fn rename_object_into_place(root_path: &path::Path, temp_path: &path::Path, hash: &str) {
+    let (prefix, _suffix) = segment_hash(hash);
+    create_prefix_dir(root_path, prefix);
+
+    match fs::rename(temp_path, object_path(root_path, hash)) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not move object into place! {}", e),
+    }
+}
+
+fn temp_object_path(root_path: &path::Path) -> path::PathBuf {
+    let nanos = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(v) => v.as_nanos(),
+        Err(_e) => 0,
+    };
+
+    root_path.join(OBJECTS_FOLDER_NAME).join(format!(
+        "{}{}_{}_{}",
+        TEMP_OBJECT_PREFIX,
+        process::id(),
+        nanos,
+        TEMP_OBJECT_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+pub fn read_object(root_path: &path::Path, hash: &str) -> Result<Object, ObjectError> {
+    let compressed = match fs::read(object_path(root_path, hash)) {
+        Ok(v) => v,
+        Err(_e) => match pack::read_packed_object(root_path, hash) {
+            Some(raw) => return Object::parse(&raw),
+            None => return Err(ObjectError::NotFound),
+        },
+    };
+
+    Object::parse(&inflate(compressed)?)
+}
+
+fn inflate(compressed: Vec<u8>) -> Result<Vec<u8>, ObjectError> {
+    let mut decoder = ZlibDecoder::new(&compressed[..]);
+    let mut raw = Vec::new();
+
+    // Objects written before compression was introduced are stored as-is,
+    // but only a complete, well-formed object is taken for one of those.
+    match decoder.read_to_end(&mut raw) {
+        Ok(_v) => Ok(raw),
+        Err(_e) if Object::parse(&compressed).is_ok() => Ok(compressed),
+        Err(e) => Err(ObjectError::Corrupt(e.to_string())),
+    }
+}
+
+pub fn read_object_of_kind(
+    root_path: &path::Path,
+    hash: &str,
+    kind: ObjectKind,
+) -> Result<Object, ObjectError> {
+    let object = read_object(root_path, hash)?;
+
+    if object.kind != kind {
+        return Err(ObjectError::KindMismatch {
+            expected: kind,
+            found: object.kind,
+        });
+    }
+
+    Ok(object)
+}
+
+pub fn object_exists(root_path: &path::Path, hash: &str) -> bool {
+    if fs::exists(object_path(root_path, hash)).unwrap_or_default() {
+        return true;
+    }
+
+    pack::is_packed(root_path, hash)
+}
+
+pub fn repack_objects(root_path: &path::Path, hashes: &[String]) -> pack::PackStats {
+    let objects = hashes
+        .iter()
+        .map(|hash| match read_object(root_path, hash) {
+            Ok(v) => (hash.clone(), Object::serialize(v.kind, &v.content)),
+            Err(e) => panic!("Could not read object {}: {}", hash, e),
+        })
+        .collect();
+
+    let stats = pack::write_pack(root_path, objects);
+
+    for hash in hashes {
+        if fs::exists(object_path(root_path, hash)).unwrap_or_default() {
+            remove_loose_object(root_path, hash);
+        }
+    }
+
+    stats
+}
+
+pub fn remove_loose_object(root_path: &path::Path, hash: &str) {
+    let path = object_path(root_path, hash);
+
+    match fs::remove_file(&path) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not remove object {}: {}", hash, e),
+    }
+
+    if let Some(prefix_dir) = path.parent() {
+        // Only succeeds once the prefix directory is empty.
+        fs::remove_dir(prefix_dir).unwrap_or_default();
+    }
+}
+
+pub fn list_loose_objects(root_path: &path::Path) -> Vec<String> {
+    let objects_path = root_path.join(OBJECTS_FOLDER_NAME);
+    let read_dir = match fs::read_dir(&objects_path) {
+        Ok(v) => v,
+        Err(_e) => panic!("Could not read objects directory!"),
+    };
+
+    let mut hashes = Vec::new();
+
+    for entry in read_dir {
+        let read_entry = entry.unwrap();
+        let prefix = read_entry.file_name().to_string_lossy().into_owned();
+
+        if prefix.len() != 2 || !read_entry.path().is_dir() {
+            continue;
+        }
+
+        let prefix_dir = match fs::read_dir(read_entry.path()) {
+            Ok(v) => v,
+            Err(_e) => panic!("Could not read objects directory!"),
+        };
+
+        for object_entry in prefix_dir {
+            let suffix = object_entry
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .into_owned();
+
+            hashes.push(format!("{}{}", prefix, suffix));
+        }
+    }
+
+    hashes.sort();
+
+    hashes
+}
+
+pub fn resolve_abbreviated_hash(
+    root_path: &path::Path,
+    abbreviation: &str,
+) -> Result<Option<String>, ObjectError> {
+    let abbreviation = abbreviation.to_lowercase();
+
+    if abbreviation.len() < MIN_ABBREVIATED_HASH_SIZE
+        || !abbreviation.chars().all(|v| v.is_ascii_hexdigit())
+    {
+        return Ok(None);
+    }
+
+    let (prefix, rest) = segment_hash(&abbreviation);
+    let mut matches: Vec<String> = Vec::new();
+
+    if let Ok(prefix_dir) = fs::read_dir(root_path.join(OBJECTS_FOLDER_NAME).join(prefix)) {
+        for entry in prefix_dir {
+            let suffix = entry.unwrap().file_name().to_string_lossy().into_owned();
+
+            if suffix.starts_with(rest) {
+                matches.push(format!("{}{}", prefix, suffix));
+            }
+        }
+    }
+
+    for hash in pack::packed_hashes(root_path) {
+        if hash.starts_with(&abbreviation) && !matches.contains(&hash) {
+            matches.push(hash);
+        }
+    }
+
+    if matches.len() > 1 {
+        return Err(ObjectError::AmbiguousHash(abbreviation));
+    }
+
+    Ok(matches.pop())
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+const BLOCK_SIZE: usize = 16;
+const COPY_OPCODE: u8 = 0x01;
+const INSERT_OPCODE: u8 = 0x02;
+
+pub fn create_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_varint(&mut delta, base.len() as u64);
+    write_varint(&mut delta, target.len() as u64);
+
+    let mut blocks: HashMap<&[u8], usize> = HashMap::new();
+    let mut offset = 0;
+    while offset + BLOCK_SIZE <= base.len() {
+        blocks
+            .entry(&base[offset..offset + BLOCK_SIZE])
+            .or_insert(offset);
+        offset += BLOCK_SIZE;
+    }
+
+    let mut pending_insert: Vec<u8> = Vec::new();
+    let mut idx = 0;
+
+    while idx < target.len() {
+        let base_offset = match target.get(idx..idx + BLOCK_SIZE) {
+            Some(block) => blocks.get(block).copied(),
+            None => None,
+        };
+
+        let base_offset = match base_offset {
+            Some(v) => v,
+            None => {
+                pending_insert.push(target[idx]);
+                idx += 1;
+                continue;
+            }
+        };
+
+        let mut length = BLOCK_SIZE;
+        while base_offset + length < base.len()
+            && idx + length < target.len()
+            && base[base_offset + length] == target[idx + length]
+        {
+            length += 1;
+        }
+
+        flush_insert(&mut delta, &mut pending_insert);
+
+        delta.push(COPY_OPCODE);
+        write_varint(&mut delta, base_offset as u64);
+        write_varint(&mut delta, length as u64);
+
+        idx += length;
+    }
+
+    flush_insert(&mut delta, &mut pending_insert);
+
+    delta
+}
+
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut cursor = 0;
+
+    let base_size = read_varint(delta, &mut cursor)? as usize;
+    let target_size = read_varint(delta, &mut cursor)? as usize;
+
+    if base_size != base.len() {
+        return None;
+    }
+
+    let mut target = Vec::with_capacity(target_size);
+
+    while cursor < delta.len() {
+        let opcode = delta[cursor];
+        cursor += 1;
+
+        match opcode {
+            COPY_OPCODE => {
+                let offset = read_varint(delta, &mut cursor)? as usize;
+                let length = read_varint(delta, &mut cursor)? as usize;
+
+                target.extend_from_slice(base.get(offset..offset.checked_add(length)?)?);
+            }
+            INSERT_OPCODE => {
+                let length = read_varint(delta, &mut cursor)? as usize;
+
+                target.extend_from_slice(delta.get(cursor..cursor.checked_add(length)?)?);
+                cursor += length;
+            }
+            _ => return None,
+        }
+    }
+
+    if target.len() != target_size {
+        return None;
+    }
+
+    Some(target)
+}
+
+fn flush_insert(delta: &mut Vec<u8>, pending_insert: &mut Vec<u8>) {
+    if pending_insert.is_empty() {
+        return;
+    }
+
+    delta.push(INSERT_OPCODE);
+    write_varint(delta, pending_insert.len() as u64);
+    delta.append(pending_insert);
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            output.push(byte);
+            return;
+        }
+
+        output.push(byte | 0x80);
+    }
+}
+
+fn read_varint(input: &[u8], cursor: &mut usize) -> Option<u64> {
+    let mut value: u64 = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = *input.get(*cursor)?;
+        *cursor += 1;
+
+        value |= ((byte & 0x7f) as u64).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(count: usize) -> Vec<u8> {
+        (0..count)
+            .flat_map(|v| format!("line number {}\n", v).into_bytes())
+            .collect()
+    }
+
+    fn assert_round_trip(base: &[u8], target: &[u8]) -> Vec<u8> {
+        let delta = create_delta(base, target);
+
+        assert_eq!(apply_delta(base, &delta).as_deref(), Some(target));
+
+        delta
+    }
+
+    #[test]
+    fn round_trips_an_edited_file_as_a_small_delta() {
+        let base = lines(200);
+        let mut target = base.clone();
+        target.splice(1000..1010, b"inserted text".iter().copied());
+        target.extend_from_slice(b"appended\n");
+
+        let delta = assert_round_trip(&base, &target);
+
+        assert!(delta.len() < target.len() / 10);
+    }
+
+    #[test]
+    fn round_trips_unrelated_and_empty_content() {
+        let base = lines(50);
+
+        assert_round_trip(&base, b"nothing in common");
+        assert_round_trip(&base, b"");
+        assert_round_trip(b"", &base);
+        assert_round_trip(b"", b"");
+        assert_round_trip(&base, &base);
+    }
+
+    #[test]
+    fn round_trips_content_shorter_than_a_block() {
+        assert_round_trip(b"short", b"shorter");
+    }
+
+    #[test]
+    fn apply_delta_rejects_another_base() {
+        let base = lines(20);
+        let delta = create_delta(&base, &lines(21));
+
+        assert_eq!(apply_delta(&base[1..], &delta), None);
+    }
+
+    #[test]
+    fn apply_delta_rejects_damaged_deltas() {
+        let base = lines(20);
+        let target = lines(30);
+        let delta = create_delta(&base, &target);
+
+        assert_eq!(apply_delta(&base, &delta[..delta.len() - 1]), None);
+        assert_eq!(apply_delta(&base, &[]), None);
+
+        let mut bad_opcode = delta.clone();
+        bad_opcode.push(0xff);
+        assert_eq!(apply_delta(&base, &bad_opcode), None);
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value);
+
+            let mut cursor = 0;
+            assert_eq!(read_varint(&bytes, &mut cursor), Some(value));
+            assert_eq!(cursor, bytes.len());
+        }
+    }
+}
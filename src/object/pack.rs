@@ -0,0 +1,541 @@
+use std::{
+    fs,
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path,
+    sync::Mutex,
+};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    constants::{PACK_FILE_EXTENSION, PACK_FILE_PREFIX, PACK_FOLDER_NAME, PACK_INDEX_FILE_NAME},
+    lockfile::LockFile,
+    object::{
+        delta::{apply_delta, create_delta},
+        Object,
+    },
+    utils::{bytes_to_hash, hash_to_bytes},
+};
+
+const PACK_MAGIC: &[u8; 4] = b"KPCK";
+const INDEX_MAGIC: &[u8; 4] = b"KIDX";
+const PACK_VERSION: u32 = 1;
+const INDEX_VERSION: u32 = 2;
+const HEADER_SIZE: u64 = 12;
+const HASH_SIZE: usize = 32;
+const INDEX_ENTRY_SIZE: u64 = HASH_SIZE as u64 + 8;
+const WHOLE_ENTRY: u8 = 0;
+const DELTA_ENTRY: u8 = 1;
+const DELTA_WINDOW: usize = 10;
+const MAX_DELTA_DEPTH: usize = 10;
+const MIN_DELTA_OBJECT_SIZE: usize = 64;
+const VERIFY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Checksums of the packs already verified by this process. A pack is named
+/// after its checksum and never rewritten, so checking it once is enough.
+static VERIFIED_PACKS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub struct PackStats {
+    pub objects: usize,
+    pub deltas: usize,
+}
+
+struct PackCandidate {
+    hash: String,
+    kind: String,
+    serialized: Vec<u8>,
+    depth: usize,
+}
+
+/// The pack the index points at, if there is one and both files match
+/// their checksums.
+pub fn pack_path(root_path: &path::Path) -> Option<path::PathBuf> {
+    let checksum = verify_pack(root_path)?;
+
+    Some(named_pack_path(root_path, &checksum))
+}
+
+fn named_pack_path(root_path: &path::Path, checksum: &str) -> path::PathBuf {
+    root_path.join(PACK_FOLDER_NAME).join(format!(
+        "{}{}.{}",
+        PACK_FILE_PREFIX, checksum, PACK_FILE_EXTENSION
+    ))
+}
+
+pub fn pack_index_path(root_path: &path::Path) -> path::PathBuf {
+    root_path.join(PACK_FOLDER_NAME).join(PACK_INDEX_FILE_NAME)
+}
+
+pub fn packed_hashes(root_path: &path::Path) -> Vec<String> {
+    if verify_pack(root_path).is_none() {
+        return Vec::new();
+    }
+
+    let index = match fs::read(pack_index_path(root_path)) {
+        Ok(v) => v,
+        Err(_e) => return Vec::new(),
+    };
+
+    let count = match read_header(&index, INDEX_MAGIC, INDEX_VERSION) {
+        Some(v) => v as usize,
+        None => return Vec::new(),
+    };
+
+    (0..count)
+        .filter_map(|idx| {
+            let start = HEADER_SIZE as usize + idx * INDEX_ENTRY_SIZE as usize;
+            index.get(start..start + HASH_SIZE).map(bytes_to_hash)
+        })
+        .collect()
+}
+
+pub fn is_packed(root_path: &path::Path, hash: &str) -> bool {
+    find_offset(root_path, hash).is_some()
+}
+
+pub fn read_packed_object(root_path: &path::Path, hash: &str) -> Option<Vec<u8>> {
+    let offset = find_offset(root_path, hash)?;
+
+    let mut pack_file = fs::File::open(pack_path(root_path)?).ok()?;
+    pack_file.seek(SeekFrom::Start(offset)).ok()?;
+
+    let mut entry_kind = [0_u8; 1];
+    pack_file.read_exact(&mut entry_kind).ok()?;
+
+    let mut base_hash = [0_u8; HASH_SIZE];
+    if entry_kind[0] == DELTA_ENTRY {
+        pack_file.read_exact(&mut base_hash).ok()?;
+    }
+
+    let mut length = [0_u8; 8];
+    pack_file.read_exact(&mut length).ok()?;
+
+    let mut compressed = vec![0_u8; u64::from_be_bytes(length) as usize];
+    pack_file.read_exact(&mut compressed).ok()?;
+
+    let mut data = Vec::new();
+    ZlibDecoder::new(&compressed[..])
+        .read_to_end(&mut data)
+        .ok()?;
+
+    match entry_kind[0] {
+        WHOLE_ENTRY => Some(data),
+        DELTA_ENTRY => {
+            let base = read_packed_object(root_path, &bytes_to_hash(&base_hash))?;
+
+            apply_delta(&base, &data)
+        }
+        _ => None,
+    }
+}
+
+pub fn write_pack(root_path: &path::Path, objects: Vec<(String, Vec<u8>)>) -> PackStats {
+    let mut candidates: Vec<PackCandidate> = objects
+        .into_iter()
+        .map(|(hash, serialized)| {
+            let kind = match Object::parse(&serialized) {
+                Ok(v) => v.kind.to_string(),
+                Err(e) => panic!("Could not pack object {}: {}", hash, e),
+            };
+
+            PackCandidate {
+                hash,
+                kind,
+                serialized,
+                depth: 0,
+            }
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| {
+        (&a.kind, a.serialized.len(), &a.hash).cmp(&(&b.kind, b.serialized.len(), &b.hash))
+    });
+
+    match fs::create_dir_all(root_path.join(PACK_FOLDER_NAME)) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not create pack folder! {}", e),
+    }
+
+    // Held until the new index is in place, so concurrent repacks can't
+    // share the temporary pack or remove each other's packs.
+    let mut index_lock = LockFile::acquire(&pack_index_path(root_path));
+
+    let temp_pack_path = root_path
+        .join(PACK_FOLDER_NAME)
+        .join(format!("{}tmp", PACK_FILE_PREFIX));
+
+    let mut pack_writer = ChecksumWriter::new(&temp_pack_path);
+    pack_writer.write(PACK_MAGIC);
+    pack_writer.write(&PACK_VERSION.to_be_bytes());
+    pack_writer.write(&(candidates.len() as u32).to_be_bytes());
+
+    let mut offsets: Vec<(String, u64)> = Vec::new();
+    let mut deltas = 0;
+
+    for idx in 0..candidates.len() {
+        let (base, delta) = find_delta_base(&candidates, idx);
+
+        offsets.push((candidates[idx].hash.clone(), pack_writer.offset));
+
+        let data = match (&base, &delta) {
+            (Some(base_idx), Some(delta)) => {
+                pack_writer.write(&[DELTA_ENTRY]);
+                pack_writer.write(&hash_to_bytes(&candidates[*base_idx].hash));
+                candidates[idx].depth = candidates[*base_idx].depth + 1;
+                deltas += 1;
+
+                compress(delta)
+            }
+            _ => {
+                pack_writer.write(&[WHOLE_ENTRY]);
+
+                compress(&candidates[idx].serialized)
+            }
+        };
+
+        pack_writer.write(&(data.len() as u64).to_be_bytes());
+        pack_writer.write(&data);
+    }
+
+    let pack_checksum = pack_writer.finish();
+    let new_pack_path = named_pack_path(root_path, &bytes_to_hash(&pack_checksum));
+
+    // The new pack gets a name of its own and only becomes visible once the
+    // index naming it replaces the old one, so a crash at any point leaves
+    // an index that matches the pack it points at.
+    match fs::rename(&temp_pack_path, &new_pack_path) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not move pack file into place! {}", e),
+    }
+
+    offsets.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut index = Vec::new();
+    index.extend_from_slice(INDEX_MAGIC);
+    index.extend_from_slice(&INDEX_VERSION.to_be_bytes());
+    index.extend_from_slice(&(offsets.len() as u32).to_be_bytes());
+
+    for (hash, offset) in &offsets {
+        index.extend_from_slice(&hash_to_bytes(hash));
+        index.extend_from_slice(&offset.to_be_bytes());
+    }
+
+    index.extend_from_slice(&pack_checksum);
+    let index_checksum = Sha256::digest(&index);
+    index.extend_from_slice(&index_checksum);
+
+    index_lock.write(&index);
+    index_lock.commit();
+
+    remove_stale_packs(root_path, &new_pack_path);
+
+    PackStats {
+        objects: candidates.len(),
+        deltas,
+    }
+}
+
+fn find_delta_base(candidates: &[PackCandidate], idx: usize) -> (Option<usize>, Option<Vec<u8>>) {
+    let target = &candidates[idx];
+
+    if target.serialized.len() < MIN_DELTA_OBJECT_SIZE {
+        return (None, None);
+    }
+
+    let mut best: (Option<usize>, Option<Vec<u8>>) = (None, None);
+    let mut best_size = target.serialized.len() / 2;
+
+    let window_start = idx.saturating_sub(DELTA_WINDOW);
+    for (base_idx, base) in candidates.iter().enumerate().take(idx).skip(window_start) {
+        if base.kind != target.kind || base.depth >= MAX_DELTA_DEPTH {
+            continue;
+        }
+
+        let delta = create_delta(&base.serialized, &target.serialized);
+        if delta.len() < best_size {
+            best_size = delta.len();
+            best = (Some(base_idx), Some(delta));
+        }
+    }
+
+    best
+}
+
+/// Packs left behind by earlier repacks, or by one that didn't finish.
+fn remove_stale_packs(root_path: &path::Path, current_pack_path: &path::Path) {
+    let read_dir = match fs::read_dir(root_path.join(PACK_FOLDER_NAME)) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not read pack folder! {}", e),
+    };
+
+    for entry in read_dir.flatten() {
+        let entry_path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        if name.starts_with(PACK_FILE_PREFIX)
+            && entry_path
+                .extension()
+                .is_some_and(|v| v == PACK_FILE_EXTENSION)
+            && entry_path != current_pack_path
+        {
+            fs::remove_file(&entry_path).unwrap_or_default();
+        }
+    }
+}
+
+/// Checks that the index and the pack it names are complete and belong
+/// together, returning the pack checksum: their headers are valid, the index
+/// has room for all its entries and the pack ends with the checksum the index
+/// recorded. Hashing their whole content is left to `verify_checksums`, as
+/// doing it on every load would cost as much as reading the pack. Anything
+/// wrong is fatal since the objects in the pack can't be found.
+fn verify_pack(root_path: &path::Path) -> Option<String> {
+    let index_path = pack_index_path(root_path);
+    let mut index_file = fs::File::open(&index_path).ok()?;
+
+    let mut pack_checksum = [0_u8; HASH_SIZE];
+    index_file.seek(SeekFrom::End(-2 * HASH_SIZE as i64)).ok()?;
+    index_file.read_exact(&mut pack_checksum).ok()?;
+    let checksum = bytes_to_hash(&pack_checksum);
+
+    let mut verified = match VERIFIED_PACKS.lock() {
+        Ok(v) => v,
+        Err(e) => e.into_inner(),
+    };
+
+    if verified.contains(&checksum) {
+        return Some(checksum);
+    }
+
+    let pack_path = named_pack_path(root_path, &checksum);
+
+    if !has_complete_index(&mut index_file) {
+        panic!("The pack index {} is corrupt!", index_path.display());
+    }
+
+    if !has_pack_trailer(&pack_path, &pack_checksum) {
+        panic!("The pack {} is missing or corrupt!", pack_path.display());
+    }
+
+    verified.push(checksum.clone());
+
+    Some(checksum)
+}
+
+fn has_complete_index(index_file: &mut fs::File) -> bool {
+    let mut header = [0_u8; HEADER_SIZE as usize];
+    let size = match index_file.metadata() {
+        Ok(v) => v.len(),
+        Err(_e) => return false,
+    };
+
+    if index_file.seek(SeekFrom::Start(0)).is_err() || index_file.read_exact(&mut header).is_err() {
+        return false;
+    }
+
+    match read_header(&header, INDEX_MAGIC, INDEX_VERSION) {
+        Some(count) => size == HEADER_SIZE + count as u64 * INDEX_ENTRY_SIZE + 2 * HASH_SIZE as u64,
+        None => false,
+    }
+}
+
+fn has_pack_trailer(pack_path: &path::Path, checksum: &[u8; HASH_SIZE]) -> bool {
+    let mut pack_file = match fs::File::open(pack_path) {
+        Ok(v) => v,
+        Err(_e) => return false,
+    };
+
+    let mut header = [0_u8; HEADER_SIZE as usize];
+    if pack_file.read_exact(&mut header).is_err()
+        || read_header(&header, PACK_MAGIC, PACK_VERSION).is_none()
+    {
+        return false;
+    }
+
+    let mut trailer = [0_u8; HASH_SIZE];
+    if pack_file.seek(SeekFrom::End(-(HASH_SIZE as i64))).is_err()
+        || pack_file.read_exact(&mut trailer).is_err()
+    {
+        return false;
+    }
+
+    trailer == *checksum
+}
+
+/// Hashes the whole index and pack and compares them with their trailing
+/// checksums, for `fsck` and before a repack rewrites the pack. Returns the
+/// files that don't match.
+pub fn verify_checksums(root_path: &path::Path) -> Vec<path::PathBuf> {
+    let index_path = pack_index_path(root_path);
+    if !fs::exists(&index_path).unwrap_or_default() {
+        return Vec::new();
+    }
+
+    let mut corrupt = Vec::new();
+
+    if !has_valid_checksum(&index_path) {
+        corrupt.push(index_path);
+    }
+
+    if let Some(pack_path) = pack_path(root_path) {
+        if !has_valid_checksum(&pack_path) {
+            corrupt.push(pack_path);
+        }
+    }
+
+    corrupt
+}
+
+fn has_valid_checksum(path: &path::Path) -> bool {
+    let file = match fs::File::open(path) {
+        Ok(v) => v,
+        Err(_e) => return false,
+    };
+    let size = match file.metadata() {
+        Ok(v) => v.len(),
+        Err(_e) => return false,
+    };
+
+    if size < HASH_SIZE as u64 {
+        return false;
+    }
+
+    let mut reader = BufReader::new(file);
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0_u8; VERIFY_BUFFER_SIZE];
+    let mut remaining = size - HASH_SIZE as u64;
+
+    while remaining > 0 {
+        let chunk = remaining.min(buffer.len() as u64) as usize;
+        if reader.read_exact(&mut buffer[..chunk]).is_err() {
+            return false;
+        }
+
+        hasher.update(&buffer[..chunk]);
+        remaining -= chunk as u64;
+    }
+
+    let mut checksum = [0_u8; HASH_SIZE];
+    if reader.read_exact(&mut checksum).is_err() {
+        return false;
+    }
+
+    hasher.finalize()[..] == checksum
+}
+
+fn find_offset(root_path: &path::Path, hash: &str) -> Option<u64> {
+    verify_pack(root_path)?;
+
+    let wanted = hash_to_bytes(hash);
+    let mut index_file = fs::File::open(pack_index_path(root_path)).ok()?;
+
+    let mut header = [0_u8; HEADER_SIZE as usize];
+    index_file.read_exact(&mut header).ok()?;
+    let count = read_header(&header, INDEX_MAGIC, INDEX_VERSION)? as u64;
+
+    let mut low = 0;
+    let mut high = count;
+    let mut entry = [0_u8; INDEX_ENTRY_SIZE as usize];
+
+    while low < high {
+        let middle = (low + high) / 2;
+
+        index_file
+            .seek(SeekFrom::Start(HEADER_SIZE + middle * INDEX_ENTRY_SIZE))
+            .ok()?;
+        index_file.read_exact(&mut entry).ok()?;
+
+        match entry[..HASH_SIZE].cmp(&wanted[..]) {
+            std::cmp::Ordering::Equal => {
+                let mut offset = [0_u8; 8];
+                offset.copy_from_slice(&entry[HASH_SIZE..]);
+
+                return Some(u64::from_be_bytes(offset));
+            }
+            std::cmp::Ordering::Less => low = middle + 1,
+            std::cmp::Ordering::Greater => high = middle,
+        }
+    }
+
+    None
+}
+
+fn read_header(bytes: &[u8], magic: &[u8; 4], expected_version: u32) -> Option<u32> {
+    if bytes.get(..4)? != magic {
+        return None;
+    }
+
+    let version = u32::from_be_bytes(bytes.get(4..8)?.try_into().ok()?);
+    if version != expected_version {
+        return None;
+    }
+
+    Some(u32::from_be_bytes(bytes.get(8..12)?.try_into().ok()?))
+}
+
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+
+    match encoder.write_all(data) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not compress pack entry! {}", e),
+    }
+
+    match encoder.finish() {
+        Ok(v) => v,
+        Err(e) => panic!("Could not compress pack entry! {}", e),
+    }
+}
+
+struct ChecksumWriter {
+    writer: BufWriter<fs::File>,
+    hasher: Sha256,
+    offset: u64,
+}
+
+impl ChecksumWriter {
+    fn new(path: &path::Path) -> ChecksumWriter {
+        let file = match fs::File::create(path) {
+            Ok(v) => v,
+            Err(e) => panic!("Could not create pack file! {}", e),
+        };
+
+        ChecksumWriter {
+            writer: BufWriter::new(file),
+            hasher: Sha256::new(),
+            offset: 0,
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+        self.offset += data.len() as u64;
+
+        match self.writer.write_all(data) {
+            Ok(v) => v,
+            Err(e) => panic!("Could not write pack file! {}", e),
+        }
+    }
+
+    fn finish(mut self) -> [u8; HASH_SIZE] {
+        let checksum: [u8; HASH_SIZE] = self.hasher.finalize().into();
+
+        match self.writer.write_all(&checksum) {
+            Ok(v) => v,
+            Err(e) => panic!("Could not write pack checksum! {}", e),
+        }
+
+        let file = match self.writer.into_inner() {
+            Ok(v) => v,
+            Err(e) => panic!("Could not flush pack file! {}", e),
+        };
+
+        match file.sync_all() {
+            Ok(v) => v,
+            Err(e) => panic!("Could not flush pack file! {}", e),
+        }
+
+        checksum
+    }
+}
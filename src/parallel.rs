@@ -0,0 +1,138 @@
+use std::{
+    fs, panic, path,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use crate::config::Config;
+
+/// A file found by `walk`, with its path relative to the repository root.
+pub struct WalkEntry {
+    pub path: path::PathBuf,
+    pub relative_path: String,
+    pub metadata: fs::Metadata,
+}
+
+/// Worker threads to use, from `core.threads`. Unset or 0 means one per core.
+pub fn thread_count(root_path: &path::Path) -> usize {
+    let configured = Config::load(root_path)
+        .core_threads
+        .parse::<usize>()
+        .unwrap_or(0);
+
+    match configured {
+        0 => thread::available_parallelism().map_or(1, |v| v.get()),
+        v => v,
+    }
+}
+
+/// Runs `task` on every item with up to `threads` workers pulling from a
+/// shared queue. Results come back in the order of `items`, whichever worker
+/// finished first.
+pub fn map<T, R, F>(items: &[T], threads: usize, task: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let threads = threads.clamp(1, items.len().max(1));
+    if threads == 1 {
+        return items.iter().map(task).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        match items.get(idx) {
+                            Some(item) => done.push((idx, task(item))),
+                            None => return done,
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| match worker.join() {
+                Ok(v) => v,
+                Err(e) => panic::resume_unwind(e),
+            })
+            .collect()
+    });
+
+    results.sort_by_key(|v| v.0);
+    results.into_iter().map(|v| v.1).collect()
+}
+
+/// Lists the files under `dir`, whose path relative to the root is
+/// `relative_dir`, reading the directories of each level in parallel.
+/// Entries for which `skip(relative_path, is_dir)` holds are neither listed
+/// nor descended into. Files are returned sorted by relative path.
+pub fn walk<F>(dir: &path::Path, relative_dir: &str, threads: usize, skip: F) -> Vec<WalkEntry>
+where
+    F: Fn(&str, bool) -> bool + Sync,
+{
+    let mut files: Vec<WalkEntry> = Vec::new();
+    let mut dirs = vec![(dir.to_path_buf(), relative_dir.to_owned())];
+
+    while !dirs.is_empty() {
+        let listings = map(&dirs, threads, |(dir, relative_dir)| {
+            read_entries(dir, relative_dir, &skip)
+        });
+
+        dirs = Vec::new();
+        for entry in listings.into_iter().flatten() {
+            match entry.metadata.is_dir() {
+                true => dirs.push((entry.path, entry.relative_path)),
+                false => files.push(entry),
+            }
+        }
+    }
+
+    files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+
+    files
+}
+
+fn read_entries<F>(dir: &path::Path, relative_dir: &str, skip: &F) -> Vec<WalkEntry>
+where
+    F: Fn(&str, bool) -> bool,
+{
+    let read_dir = match fs::read_dir(dir) {
+        Ok(v) => v,
+        Err(_e) => panic!("Could not read directory!"),
+    };
+
+    let mut entries = Vec::new();
+
+    for entry in read_dir {
+        let entry_path = entry.unwrap().path();
+        let metadata = fs::metadata(&entry_path).unwrap();
+
+        let name = entry_path.file_name().unwrap_or_default().to_string_lossy();
+        let relative_path = match relative_dir.is_empty() {
+            true => name.to_string(),
+            false => format!("{}/{}", relative_dir, name),
+        };
+
+        if skip(&relative_path, metadata.is_dir()) || !(metadata.is_dir() || metadata.is_file()) {
+            continue;
+        }
+
+        entries.push(WalkEntry {
+            path: entry_path,
+            relative_path,
+            metadata,
+        });
+    }
+
+    entries
+}
@@ -0,0 +1,187 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs, path, process,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    commit::Commit,
+    object::{
+        list_loose_objects, object_path,
+        pack::{pack_path, packed_hashes},
+        read_object, ObjectKind,
+    },
+    refs::{list_refs, read_merge_head, read_reflog_hashes, resolve_head},
+    staging_area::StagingArea,
+    tag::Tag,
+    tree::parse_tree,
+};
+
+const DEFAULT_EXPIRE_DAYS: u64 = 14;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+pub struct PruneOptions {
+    pub dry_run: bool,
+    pub expire: Duration,
+}
+
+pub struct UnreachableObjects {
+    pub expired: Vec<String>,
+    pub recent: Vec<String>,
+}
+
+impl PruneOptions {
+    pub fn from_args(args: &[String]) -> PruneOptions {
+        let mut options = PruneOptions {
+            dry_run: false,
+            expire: Duration::from_secs(DEFAULT_EXPIRE_DAYS * SECONDS_PER_DAY),
+        };
+
+        let mut args_iter = args.iter();
+        while let Some(arg) = args_iter.next() {
+            match &arg[..] {
+                "-n" | "--dry-run" => options.dry_run = true,
+                "--expire" => {
+                    let value = args_iter.next().map(|v| &v[..]);
+
+                    options.expire = match value {
+                        Some("now") => Duration::ZERO,
+                        Some(days) => match days.parse::<u64>() {
+                            Ok(v) => Duration::from_secs(v * SECONDS_PER_DAY),
+                            Err(_e) => {
+                                println!("The --expire value must be a number of days or `now`");
+                                process::exit(1);
+                            }
+                        },
+                        None => {
+                            println!("You must pass a number of days after --expire");
+                            process::exit(1);
+                        }
+                    };
+                }
+                _ => {
+                    println!("Unknown argument: {}", arg);
+                    process::exit(1);
+                }
+            }
+        }
+
+        options
+    }
+}
+
+pub fn collect_roots(root_path: &path::Path) -> Vec<String> {
+    let mut roots: Vec<String> = resolve_head(root_path).into_iter().collect();
+
+    roots.extend(list_refs(root_path).into_iter().map(|(_name, hash)| hash));
+    roots.extend(read_reflog_hashes(root_path));
+    roots.extend(read_merge_head(root_path));
+
+    let staging_area = StagingArea::open(root_path.to_path_buf());
+    roots.extend(staging_area.entries().values().cloned());
+
+    for conflict in staging_area.conflicts().values() {
+        roots.extend(
+            conflict
+                .stages()
+                .into_iter()
+                .map(|(_stage, hash)| hash.clone()),
+        );
+    }
+
+    roots
+}
+
+pub fn mark_reachable(root_path: &path::Path, roots: Vec<String>) -> HashSet<String> {
+    let mut reachable = HashSet::new();
+    let mut pending = roots;
+
+    while let Some(hash) = pending.pop() {
+        if !reachable.insert(hash.clone()) {
+            continue;
+        }
+
+        let object = match read_object(root_path, &hash) {
+            Ok(v) => v,
+            Err(_e) => continue,
+        };
+
+        match object.kind {
+            ObjectKind::Commit => {
+                if let Some(commit) = Commit::parse(&object.text()) {
+                    pending.push(commit.tree);
+                    pending.extend(commit.parents);
+                }
+            }
+            ObjectKind::Tree => {
+                for entry in parse_tree(&object.text()).unwrap_or_default() {
+                    if entry.kind == ObjectKind::Blob {
+                        reachable.insert(entry.hash);
+                        continue;
+                    }
+
+                    pending.push(entry.hash);
+                }
+            }
+            ObjectKind::Tag => {
+                if let Some(tag) = Tag::parse(&object.text()) {
+                    pending.push(tag.object);
+                }
+            }
+            ObjectKind::Blob => (),
+        }
+    }
+
+    reachable
+}
+
+pub fn all_objects(root_path: &path::Path) -> Vec<String> {
+    let hashes: BTreeSet<String> = list_loose_objects(root_path)
+        .into_iter()
+        .chain(packed_hashes(root_path))
+        .collect();
+
+    hashes.into_iter().collect()
+}
+
+pub fn find_unreachable(
+    root_path: &path::Path,
+    reachable: &HashSet<String>,
+    expire: Duration,
+) -> UnreachableObjects {
+    let mut unreachable = UnreachableObjects {
+        expired: Vec::new(),
+        recent: Vec::new(),
+    };
+
+    for hash in all_objects(root_path) {
+        if reachable.contains(&hash) {
+            continue;
+        }
+
+        let loose_path = object_path(root_path, &hash);
+        let age_path = match fs::exists(&loose_path).unwrap_or_default() {
+            true => loose_path,
+            false => pack_path(root_path).unwrap_or_default(),
+        };
+
+        if object_age(&age_path) >= expire {
+            unreachable.expired.push(hash);
+        } else {
+            unreachable.recent.push(hash);
+        }
+    }
+
+    unreachable
+}
+
+fn object_age(path: &path::Path) -> Duration {
+    let modified = match fs::metadata(path).and_then(|v| v.modified()) {
+        Ok(v) => v,
+        Err(_e) => return Duration::ZERO,
+    };
+
+    SystemTime::now()
+        .duration_since(modified)
+        .unwrap_or(Duration::ZERO)
+}
@@ -0,0 +1,404 @@
+use std::{
+    fs,
+    io::Write,
+    path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    constants::{
+        HEAD_FILE_PATH, LOGS_FOLDER_NAME, MERGE_HEAD_FILE_PATH, REFS_FOLDER_NAME, ROOT_FOLDER_NAME,
+    },
+    lockfile::LockFile,
+    object::{resolve_abbreviated_hash, ObjectError},
+    tag::Tag,
+};
+
+const SYMBOLIC_REF_PREFIX: &str = "ref: ";
+const HEAD_REFLOG_NAME: &str = "HEAD";
+const BRANCH_REF_PREFIX: &str = "refs/";
+const TAGS_FOLDER_NAME: &str = "tags";
+const NULL_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+pub fn read_head(root_path: &path::Path) -> String {
+    let head_path = root_path.join(HEAD_FILE_PATH);
+
+    let head_content = match fs::read_to_string(&head_path) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not read HEAD file! {}", e),
+    };
+
+    head_content.trim().to_owned()
+}
+
+pub fn head_ref_name(root_path: &path::Path) -> Option<String> {
+    let head_content = read_head(root_path);
+
+    head_content
+        .strip_prefix(SYMBOLIC_REF_PREFIX)
+        .map(|v| v.to_owned())
+}
+
+pub fn read_ref(root_path: &path::Path, ref_name: &str) -> Option<String> {
+    let ref_path = root_path.join(ROOT_FOLDER_NAME).join(ref_name);
+
+    match fs::read_to_string(&ref_path) {
+        Ok(v) if !v.trim().is_empty() => Some(v.trim().to_owned()),
+        _ => None,
+    }
+}
+
+pub fn resolve_head(root_path: &path::Path) -> Option<String> {
+    match head_ref_name(root_path) {
+        Some(ref_name) => read_ref(root_path, &ref_name),
+        None => Some(read_head(root_path)),
+    }
+}
+
+/// Resolves a revision to the commit it names, peeling annotated tags.
+/// Fails when an abbreviated hash matches more than one object.
+pub fn resolve_revision(
+    root_path: &path::Path,
+    revision: &str,
+) -> Result<Option<String>, ObjectError> {
+    let hash = resolve_object(root_path, revision)?;
+
+    Ok(hash.map(|v| Tag::peel(root_path, &v)))
+}
+
+/// Resolves a revision to the object it names, without peeling tags.
+pub fn resolve_object(
+    root_path: &path::Path,
+    revision: &str,
+) -> Result<Option<String>, ObjectError> {
+    if revision == HEAD_REFLOG_NAME {
+        return Ok(resolve_head(root_path));
+    }
+
+    let ref_names = match revision.starts_with(BRANCH_REF_PREFIX) {
+        true => vec![revision.to_owned()],
+        false => vec![branch_ref_name(revision), tag_ref_name(revision)],
+    };
+
+    for ref_name in ref_names {
+        if let Some(hash) = read_ref(root_path, &ref_name) {
+            return Ok(Some(hash));
+        }
+    }
+
+    resolve_abbreviated_hash(root_path, revision)
+}
+
+pub fn read_merge_head(root_path: &path::Path) -> Option<String> {
+    match fs::read_to_string(root_path.join(MERGE_HEAD_FILE_PATH)) {
+        Ok(v) if !v.trim().is_empty() => Some(v.trim().to_owned()),
+        _ => None,
+    }
+}
+
+pub fn update_head(root_path: &path::Path, commit_hash: &str, reflog_message: &str) {
+    let old_hash = resolve_head(root_path).unwrap_or(NULL_HASH.to_owned());
+
+    match head_ref_name(root_path) {
+        Some(ref_name) => write_ref(root_path, &ref_name, commit_hash, reflog_message),
+        None => write_locked(&root_path.join(HEAD_FILE_PATH), commit_hash),
+    }
+
+    append_reflog(
+        root_path,
+        HEAD_REFLOG_NAME,
+        &old_hash,
+        commit_hash,
+        reflog_message,
+    );
+}
+
+pub fn set_head_to_branch(root_path: &path::Path, branch: &str) {
+    let content = format!("{}{}", SYMBOLIC_REF_PREFIX, branch_ref_name(branch));
+
+    write_locked(&root_path.join(HEAD_FILE_PATH), &content);
+}
+
+pub fn checkout_branch_head(root_path: &path::Path, branch: &str, reflog_message: &str) {
+    let old_hash = resolve_head(root_path).unwrap_or(NULL_HASH.to_owned());
+
+    set_head_to_branch(root_path, branch);
+
+    if let Some(new_hash) = resolve_head(root_path) {
+        append_reflog(
+            root_path,
+            HEAD_REFLOG_NAME,
+            &old_hash,
+            &new_hash,
+            reflog_message,
+        );
+    }
+}
+
+pub fn detach_head(root_path: &path::Path, commit_hash: &str, reflog_message: &str) {
+    let old_hash = resolve_head(root_path).unwrap_or(NULL_HASH.to_owned());
+
+    write_locked(&root_path.join(HEAD_FILE_PATH), commit_hash);
+    append_reflog(
+        root_path,
+        HEAD_REFLOG_NAME,
+        &old_hash,
+        commit_hash,
+        reflog_message,
+    );
+}
+
+pub fn branch_ref_name(branch: &str) -> String {
+    format!("{}{}", BRANCH_REF_PREFIX, branch)
+}
+
+pub fn tag_ref_name(tag: &str) -> String {
+    format!("{}{}/{}", BRANCH_REF_PREFIX, TAGS_FOLDER_NAME, tag)
+}
+
+pub fn current_branch(root_path: &path::Path) -> Option<String> {
+    head_ref_name(root_path).map(|v| v.trim_start_matches(BRANCH_REF_PREFIX).to_owned())
+}
+
+pub fn is_valid_branch_name(branch: &str) -> bool {
+    !branch.is_empty()
+        && branch != HEAD_REFLOG_NAME
+        && branch != TAGS_FOLDER_NAME
+        && !branch.starts_with(&format!("{}/", TAGS_FOLDER_NAME))
+        && !branch.starts_with('-')
+        && !branch.starts_with('/')
+        && !branch.ends_with('/')
+        && !branch.ends_with(".lock")
+        && !branch.contains("..")
+        && !branch.contains("//")
+        && !branch
+            .chars()
+            .any(|v| v.is_whitespace() || v.is_control() || "~^:?*[\\".contains(v))
+}
+
+pub fn ref_name_conflicts(root_path: &path::Path, ref_name: &str) -> bool {
+    let ref_path = root_path.join(ROOT_FOLDER_NAME).join(ref_name);
+    let refs_path = root_path.join(REFS_FOLDER_NAME);
+
+    if fs::exists(&ref_path).unwrap_or_default() {
+        return true;
+    }
+
+    ref_path
+        .ancestors()
+        .skip(1)
+        .take_while(|v| *v != refs_path)
+        .any(|v| v.is_file())
+}
+
+pub fn list_branches(root_path: &path::Path) -> Vec<(String, String)> {
+    let tags_prefix = format!("{}{}/", BRANCH_REF_PREFIX, TAGS_FOLDER_NAME);
+
+    list_refs(root_path)
+        .into_iter()
+        .filter(|(ref_name, _hash)| !ref_name.starts_with(&tags_prefix))
+        .map(|(ref_name, hash)| {
+            (
+                ref_name.trim_start_matches(BRANCH_REF_PREFIX).to_owned(),
+                hash,
+            )
+        })
+        .collect()
+}
+
+pub fn list_tags(root_path: &path::Path) -> Vec<(String, String)> {
+    let tags_prefix = format!("{}{}/", BRANCH_REF_PREFIX, TAGS_FOLDER_NAME);
+
+    list_refs(root_path)
+        .into_iter()
+        .filter_map(|(ref_name, hash)| {
+            ref_name
+                .strip_prefix(&tags_prefix)
+                .map(|name| (name.to_owned(), hash))
+        })
+        .collect()
+}
+
+pub fn write_ref(root_path: &path::Path, ref_name: &str, hash: &str, reflog_message: &str) {
+    let ref_path = root_path.join(ROOT_FOLDER_NAME).join(ref_name);
+
+    // The old value is read under the lock so the reflog can't miss an update.
+    let mut lock = LockFile::acquire(&ref_path);
+    let old_hash = read_ref(root_path, ref_name).unwrap_or(NULL_HASH.to_owned());
+
+    lock.write(hash.as_bytes());
+    lock.commit();
+    append_reflog(root_path, ref_name, &old_hash, hash, reflog_message);
+}
+
+pub fn delete_ref(root_path: &path::Path, ref_name: &str) {
+    let ref_path = root_path.join(ROOT_FOLDER_NAME).join(ref_name);
+
+    match LockFile::acquire(&ref_path).delete_target() {
+        Ok(v) => v,
+        Err(e) => panic!("Could not delete ref {}: {}", ref_name, e),
+    }
+
+    fs::remove_file(root_path.join(LOGS_FOLDER_NAME).join(ref_name)).unwrap_or_default();
+    remove_empty_parents(&ref_path, &root_path.join(REFS_FOLDER_NAME));
+}
+
+pub fn rename_ref(root_path: &path::Path, old_ref_name: &str, new_ref_name: &str) {
+    let hash = match read_ref(root_path, old_ref_name) {
+        Some(v) => v,
+        None => panic!("Could not read ref {}!", old_ref_name),
+    };
+
+    let old_log_path = root_path.join(LOGS_FOLDER_NAME).join(old_ref_name);
+    let new_log_path = root_path.join(LOGS_FOLDER_NAME).join(new_ref_name);
+
+    if fs::exists(&old_log_path).unwrap_or_default() {
+        if let Some(parent) = new_log_path.parent() {
+            fs::create_dir_all(parent).unwrap_or_default();
+        }
+
+        match fs::rename(&old_log_path, &new_log_path) {
+            Ok(v) => v,
+            Err(e) => panic!("Could not move reflog of {}: {}", old_ref_name, e),
+        }
+    }
+
+    write_ref(
+        root_path,
+        new_ref_name,
+        &hash,
+        &format!("branch: renamed {} to {}", old_ref_name, new_ref_name),
+    );
+
+    let old_ref_path = root_path.join(ROOT_FOLDER_NAME).join(old_ref_name);
+    match fs::remove_file(&old_ref_path) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not delete ref {}: {}", old_ref_name, e),
+    }
+
+    remove_empty_parents(&old_ref_path, &root_path.join(REFS_FOLDER_NAME));
+}
+
+fn write_locked(path: &path::Path, content: &str) {
+    let mut lock = LockFile::acquire(path);
+
+    lock.write(content.as_bytes());
+    lock.commit();
+}
+
+pub fn remove_empty_parents(path: &path::Path, stop_at: &path::Path) {
+    let mut current = path.parent();
+
+    while let Some(dir) = current {
+        // Only succeeds while the directory is empty.
+        if dir == stop_at || fs::remove_dir(dir).is_err() {
+            return;
+        }
+
+        current = dir.parent();
+    }
+}
+
+pub fn append_reflog(
+    root_path: &path::Path,
+    ref_name: &str,
+    old_hash: &str,
+    new_hash: &str,
+    message: &str,
+) {
+    let log_path = root_path.join(LOGS_FOLDER_NAME).join(ref_name);
+
+    if let Some(parent) = log_path.parent() {
+        match fs::create_dir_all(parent) {
+            Ok(v) => v,
+            Err(e) => panic!("Could not create logs folder! {}", e),
+        }
+    }
+
+    let timestamp = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(v) => v.as_secs(),
+        Err(_e) => 0,
+    };
+
+    let mut file = match fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+    {
+        Ok(v) => v,
+        Err(e) => panic!("Could not open reflog! {}", e),
+    };
+
+    let line = format!("{} {} {}\t{}\n", old_hash, new_hash, timestamp, message);
+
+    match file.write_all(line.as_bytes()) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not write reflog! {}", e),
+    }
+}
+
+pub fn read_reflog_hashes(root_path: &path::Path) -> Vec<String> {
+    let mut hashes = Vec::new();
+
+    for log_path in list_files(&root_path.join(LOGS_FOLDER_NAME)) {
+        let content = fs::read_to_string(&log_path).unwrap_or_default();
+
+        for line in content.lines() {
+            let mut splitted = line.split(' ');
+
+            for hash in [splitted.next(), splitted.next()].into_iter().flatten() {
+                if hash != NULL_HASH {
+                    hashes.push(hash.to_owned());
+                }
+            }
+        }
+    }
+
+    hashes
+}
+
+pub fn list_refs(root_path: &path::Path) -> Vec<(String, String)> {
+    let root_dir = root_path.join(ROOT_FOLDER_NAME);
+    let mut refs = Vec::new();
+
+    for ref_path in list_files(&root_path.join(REFS_FOLDER_NAME)) {
+        let ref_name = match ref_path.strip_prefix(&root_dir) {
+            Ok(v) => format!("{}", v.display()),
+            Err(_e) => continue,
+        };
+
+        if LockFile::is_lock_path(&ref_path) {
+            continue;
+        }
+
+        if let Some(hash) = read_ref(root_path, &ref_name) {
+            refs.push((ref_name, hash));
+        }
+    }
+
+    refs.sort();
+
+    refs
+}
+
+fn list_files(path: &path::Path) -> Vec<path::PathBuf> {
+    let read_dir = match fs::read_dir(path) {
+        Ok(v) => v,
+        Err(_e) => return Vec::new(),
+    };
+
+    let mut files = Vec::new();
+
+    for entry in read_dir {
+        let entry_path = entry.unwrap().path();
+
+        if entry_path.is_dir() {
+            files.extend(list_files(&entry_path));
+            continue;
+        }
+
+        files.push(entry_path);
+    }
+
+    files
+}
@@ -1,38 +1,140 @@
 use std::{
-    collections::HashMap,
-    fs,
-    io::{Read, Write},
-    path,
+    collections::HashMap, fs, io::Read, os::unix::fs::MetadataExt, path, process, time::SystemTime,
 };
 
-use crate::constants::INDEX_FILE_PATH;
+use crate::{constants::INDEX_FILE_PATH, lockfile::LockFile, staging_area::index_file::IndexEntry};
+
+mod index_file;
+
+/// The three versions of a path left unresolved by a merge, stored in the
+/// index as entries with stages 1 (base), 2 (ours) and 3 (theirs).
+#[derive(Clone, Default)]
+pub struct ConflictEntry {
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+/// The stat data of a file when its hash was stored in the index. While it
+/// still matches the file there is no need to hash it again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileStat {
+    pub size: u64,
+    pub mtime: i64,
+    pub mtime_nsec: i64,
+    pub ctime: i64,
+    pub ctime_nsec: i64,
+    pub inode: u64,
+    pub mode: u32,
+}
 
 pub struct StagingArea {
     values: HashMap<String, String>,
+    conflicts: HashMap<String, ConflictEntry>,
+    stats: HashMap<String, FileStat>,
+    /// Modification time (in seconds) of the index file when it was read.
+    timestamp: i64,
+    /// Stat data of the index file when it was read, to notice other
+    /// processes replacing it before this one saves without holding the lock.
+    snapshot: Option<FileStat>,
+    lock: Option<LockFile>,
+    /// Whether the entries changed since the index was read or last saved.
+    modified: bool,
     root_path: path::PathBuf,
 }
 
+impl FileStat {
+    pub fn from_metadata(metadata: &fs::Metadata) -> FileStat {
+        FileStat {
+            size: metadata.size(),
+            mtime: metadata.mtime(),
+            mtime_nsec: metadata.mtime_nsec(),
+            ctime: metadata.ctime(),
+            ctime_nsec: metadata.ctime_nsec(),
+            inode: metadata.ino(),
+            mode: metadata.mode(),
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<FileStat> {
+        let fields: Vec<&str> = value.split(':').collect();
+        if fields.len() != 7 {
+            return None;
+        }
+
+        Some(FileStat {
+            size: fields[0].parse().ok()?,
+            mtime: fields[1].parse().ok()?,
+            mtime_nsec: fields[2].parse().ok()?,
+            ctime: fields[3].parse().ok()?,
+            ctime_nsec: fields[4].parse().ok()?,
+            inode: fields[5].parse().ok()?,
+            mode: fields[6].parse().ok()?,
+        })
+    }
+}
+
+impl ConflictEntry {
+    pub fn stages(&self) -> Vec<(u8, &String)> {
+        [(1, &self.base), (2, &self.ours), (3, &self.theirs)]
+            .into_iter()
+            .filter_map(|(stage, hash)| hash.as_ref().map(|v| (stage, v)))
+            .collect()
+    }
+
+    fn set_stage(&mut self, stage: u8, hash: &str) {
+        let slot = match stage {
+            1 => &mut self.base,
+            2 => &mut self.ours,
+            3 => &mut self.theirs,
+            _ => panic!("Unknown conflict stage {} in the index!", stage),
+        };
+
+        *slot = Some(hash.to_owned());
+    }
+}
+
 impl StagingArea {
-    fn open_file(&self, write: bool) -> fs::File {
-        let mut index_path = self.root_path.clone();
-        index_path.extend(&[INDEX_FILE_PATH]);
-
-        let file = match fs::OpenOptions::new()
-            .write(write)
-            .truncate(write)
-            .read(true)
-            .open(&index_path)
-        {
+    fn index_path(&self) -> path::PathBuf {
+        self.root_path.join(INDEX_FILE_PATH)
+    }
+
+    fn open_file(&self) -> fs::File {
+        match fs::File::open(self.index_path()) {
             Ok(v) => v,
             Err(e) => panic!("Could not open index file! {}", e),
+        }
+    }
+
+    /// Reads the index for commands that only look at it.
+    pub fn open(root_path: path::PathBuf) -> StagingArea {
+        let mut staging_area = Self {
+            values: HashMap::new(),
+            conflicts: HashMap::new(),
+            stats: HashMap::new(),
+            timestamp: 0,
+            snapshot: None,
+            lock: None,
+            modified: false,
+            root_path,
         };
 
-        file
+        staging_area.read();
+
+        staging_area
     }
 
-    pub fn open(root_path: path::PathBuf) -> StagingArea {
+    /// Takes `index.lock` before reading the index, so no other kvc process
+    /// can change it until this one saves it (or drops it unchanged).
+    pub fn lock(root_path: path::PathBuf) -> StagingArea {
         let mut staging_area = Self {
             values: HashMap::new(),
+            conflicts: HashMap::new(),
+            stats: HashMap::new(),
+            timestamp: 0,
+            snapshot: None,
+            lock: Some(LockFile::acquire(&root_path.join(INDEX_FILE_PATH))),
+            modified: false,
             root_path,
         };
 
@@ -42,73 +144,298 @@ impl StagingArea {
     }
 
     pub fn read(&mut self) -> HashMap<String, String> {
-        let mut file = self.open_file(false);
+        let mut file = self.open_file();
+
+        let metadata = match file.metadata() {
+            Ok(v) => v,
+            Err(e) => panic!("Could not read index metadata! {}", e),
+        };
+        self.timestamp = metadata.mtime();
+        self.snapshot = Some(FileStat::from_metadata(&metadata));
 
-        let mut file_content = String::new();
-        match file.read_to_string(&mut file_content) {
+        let mut file_content = Vec::new();
+        match file.read_to_end(&mut file_content) {
             Ok(v) => v,
             Err(_e) => panic!("Could not read index content!"),
         };
 
-        if file_content == "" {
+        if file_content.is_empty() {
             return self.values.clone();
         }
 
-        for line in file_content.split("\n") {
-            let (key, value) = Self::get_key_value(line);
+        let is_legacy = index_file::is_legacy(&file_content);
+        let entries = match is_legacy {
+            true => index_file::decode_legacy(&String::from_utf8_lossy(&file_content)).0,
+            false => match index_file::decode(&file_content) {
+                Ok(v) => v,
+                Err(e) => {
+                    // Exiting skips destructors, so the lock is released first.
+                    self.lock = None;
+                    println!("The index file is corrupt: {}!", e);
+                    println!("Run `kvc fsck` for details.");
+                    process::exit(1);
+                }
+            },
+        };
 
-            self.values.insert(key.to_owned(), value.to_owned());
+        for entry in entries {
+            if entry.stage != 0 {
+                self.conflicts
+                    .entry(entry.path)
+                    .or_default()
+                    .set_stage(entry.stage, &entry.hash);
+                continue;
+            }
+
+            if let Some(stat) = entry.stat {
+                self.stats.insert(entry.path.clone(), stat);
+            }
+            self.values.insert(entry.path, entry.hash);
+        }
+
+        // Indexes written in the old text format are upgraded on first read.
+        if is_legacy {
+            self.try_save();
         }
 
         self.values.clone()
     }
 
-    fn get_key_value(str: &str) -> (&str, &str) {
-        let mut splitted = str.split("||");
+    /// Reads the index without panicking, for fsck: returns every entry (with
+    /// conflict stages as `path:stage` keys) and a description of each problem.
+    pub fn inspect(root_path: path::PathBuf) -> (HashMap<String, String>, Vec<String>) {
+        let staging_area = Self {
+            values: HashMap::new(),
+            conflicts: HashMap::new(),
+            stats: HashMap::new(),
+            timestamp: 0,
+            snapshot: None,
+            lock: None,
+            modified: false,
+            root_path,
+        };
+
+        let mut file = staging_area.open_file();
+        let mut file_content = Vec::new();
+        match file.read_to_end(&mut file_content) {
+            Ok(v) => v,
+            Err(_e) => panic!("Could not read index content!"),
+        };
 
-        let key = splitted.next().expect("No key was found!");
-        let value = splitted.next().expect("No value was found!");
+        let (entries, problems) = match index_file::is_legacy(&file_content) {
+            true => {
+                let text = String::from_utf8_lossy(&file_content);
+                let (entries, malformed) = index_file::decode_legacy(&text);
+                let problems = malformed
+                    .into_iter()
+                    .map(|(number, content)| format!("{} {:?}", number, content))
+                    .collect();
 
-        (key, value)
+                (entries, problems)
+            }
+            false => match index_file::decode(&file_content) {
+                Ok(v) => (v, Vec::new()),
+                Err(e) => (Vec::new(), vec![e.to_string()]),
+            },
+        };
+
+        let values = entries
+            .into_iter()
+            .map(|entry| match entry.stage {
+                0 => (entry.path, entry.hash),
+                stage => (format!("{}:{}", entry.path, stage), entry.hash),
+            })
+            .collect();
+
+        (values, problems)
     }
 
-    fn save(&self) {
-        let mut file = self.open_file(true);
-        let mut file_content: String = "".to_owned();
+    /// Writes the changes made through `add`, `remove`, `remove_deleted` and
+    /// `set_entries` in one go, through the index lock (taken first unless
+    /// this staging area already holds it). Without changes it only releases
+    /// the lock.
+    pub fn save(&mut self) {
+        if !self.modified {
+            self.lock = None;
+            return;
+        }
 
-        let mut idx = 0;
-        for (key, value) in self.values.iter() {
-            if idx == 0 {
-                file_content = format!("{}||{}", &key, &value);
-            } else {
-                file_content = format!("{}\n{}||{}", file_content, &key, &value);
-            }
+        let lock = match self.lock.take() {
+            Some(v) => v,
+            None => LockFile::acquire(&self.index_path()),
+        };
 
-            idx += 1;
+        if !self.is_snapshot_current() {
+            // Exiting skips destructors, so the lock is released first.
+            drop(lock);
+            println!("The index was changed by another kvc process, not saving it!");
+            process::exit(1);
         }
 
-        match file.write_all(file_content.as_bytes()) {
-            Ok(v) => v,
-            Err(_e) => panic!("Could not write to index file!"),
+        self.write(lock);
+    }
+
+    /// Saves only if no other process is holding the lock, for updates that
+    /// are just an optimization and can be skipped. A staging area holding
+    /// the lock keeps it and leaves the write to its own `save`.
+    fn try_save(&mut self) {
+        if self.lock.is_some() {
+            self.modified = true;
+            return;
+        }
+
+        if let Some(lock) = LockFile::try_acquire(&self.index_path()) {
+            if self.is_snapshot_current() {
+                self.write(lock);
+            }
         }
     }
 
-    pub fn get(&self, path: String) -> String {
-        match self.values.get(&path) {
-            Some(v) => v.clone(),
-            None => String::new(),
+    fn is_snapshot_current(&self) -> bool {
+        let current = fs::metadata(self.index_path())
+            .ok()
+            .map(|v| FileStat::from_metadata(&v));
+
+        current == self.snapshot
+    }
+
+    fn write(&mut self, mut lock: LockFile) {
+        // Racy timestamps: a file changed in the same second the index is
+        // written could keep its stat data, so its stat isn't trusted.
+        let now = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(v) => v.as_secs() as i64,
+            Err(e) => panic!("Could not read the current time! {}", e),
+        };
+
+        let mut entries: Vec<IndexEntry> = self
+            .values
+            .iter()
+            .map(|(path, hash)| IndexEntry {
+                path: path.clone(),
+                stage: 0,
+                hash: hash.clone(),
+                stat: self.stats.get(path).filter(|v| v.mtime < now).copied(),
+            })
+            .collect();
+
+        for (path, conflict) in &self.conflicts {
+            for (stage, hash) in conflict.stages() {
+                entries.push(IndexEntry {
+                    path: path.clone(),
+                    stage,
+                    hash: hash.clone(),
+                    stat: None,
+                });
+            }
         }
+
+        lock.write(&index_file::encode(entries));
+        lock.commit();
+
+        self.modified = false;
+
+        // Later saves of this staging area must not trip over its own write.
+        self.snapshot = fs::metadata(self.index_path())
+            .ok()
+            .map(|v| FileStat::from_metadata(&v));
     }
 
-    pub fn add(&mut self, path: String, hash: String) {
+    pub fn entries(&self) -> &HashMap<String, String> {
+        &self.values
+    }
+
+    /// Stores `hash` for `path` along with the stat data of the file, which
+    /// must be taken before hashing it so later changes aren't missed.
+    pub fn add(&mut self, path: String, hash: String, stat: FileStat) {
+        self.conflicts.remove(&path);
+        self.stats.insert(path.clone(), stat);
         self.values.insert(path, hash);
 
-        self.save();
+        self.modified = true;
+    }
+
+    /// Whether the file at `path` still matches the index without hashing it.
+    /// Files changed in the same second the index was written are never
+    /// trusted, since a later change could have kept the same stat data.
+    pub fn is_unchanged(&self, path: &str, metadata: &fs::Metadata) -> bool {
+        match self.stats.get(path) {
+            Some(stat) => *stat == FileStat::from_metadata(metadata) && stat.mtime < self.timestamp,
+            None => false,
+        }
+    }
+
+    /// Records the stat data of files whose content was checked against the
+    /// index, so the next run can skip hashing them.
+    pub fn refresh(&mut self, stats: HashMap<String, FileStat>) {
+        if stats.is_empty() {
+            return;
+        }
+
+        for (path, stat) in stats {
+            if self.values.contains_key(&path) {
+                self.stats.insert(path, stat);
+            }
+        }
+
+        self.try_save();
+    }
+
+    pub fn conflicts(&self) -> &HashMap<String, ConflictEntry> {
+        &self.conflicts
+    }
+
+    pub fn set_entries(
+        &mut self,
+        values: HashMap<String, String>,
+        conflicts: HashMap<String, ConflictEntry>,
+    ) {
+        // Only files whose hash didn't change keep their stat data.
+        self.stats.retain(|path, _| {
+            values
+                .get(path)
+                .is_some_and(|v| self.values.get(path) == Some(v))
+        });
+        self.values = values;
+        self.conflicts = conflicts;
+
+        self.modified = true;
     }
 
     pub fn remove(&mut self, path: String) {
+        self.conflicts.remove(&path);
+        self.stats.remove(&path);
         self.values.remove(&path);
 
-        self.save();
+        self.modified = true;
+    }
+
+    /// Drops the entries at or below `path` (relative to the root, empty for
+    /// everything) whose file is gone from the working tree, returning them.
+    pub fn remove_deleted(&mut self, path: &str) -> Vec<String> {
+        let dir_prefix = format!("{}/", path);
+
+        let mut deleted: Vec<String> = self
+            .values
+            .keys()
+            .filter(|v| path.is_empty() || **v == path || v.starts_with(&dir_prefix))
+            .filter(|v| !self.root_path.join(v).is_file())
+            .cloned()
+            .collect();
+
+        deleted.sort();
+
+        if deleted.is_empty() {
+            return deleted;
+        }
+
+        for file_path in &deleted {
+            self.conflicts.remove(file_path);
+            self.stats.remove(file_path);
+            self.values.remove(file_path);
+        }
+
+        self.modified = true;
+
+        deleted
     }
 }
@@ -0,0 +1,378 @@
+use std::fmt;
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    staging_area::FileStat,
+    utils::{bytes_to_hash, hash_to_bytes},
+};
+
+const INDEX_MAGIC: &[u8; 4] = b"KVCI";
+const INDEX_VERSION: u32 = 1;
+const HEADER_SIZE: usize = 12;
+const HASH_SIZE: usize = 32;
+const CHECKSUM_SIZE: usize = 32;
+const HAS_STAT_FLAG: u8 = 1;
+
+/// One line of the index: a path at a given stage (0 for regular entries,
+/// 1 to 3 for the sides of a conflict) with the stat data of stage 0 files.
+pub struct IndexEntry {
+    pub path: String,
+    pub stage: u8,
+    pub hash: String,
+    pub stat: Option<FileStat>,
+}
+
+#[derive(Debug)]
+pub enum IndexError {
+    UnknownFormat,
+    UnsupportedVersion(u32),
+    Truncated,
+    ChecksumMismatch,
+    InvalidStage { path: String, stage: u8 },
+    InvalidPath(String),
+    Unsorted(String),
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexError::UnknownFormat => write!(
+                f,
+                "index is neither a kvc index nor a valid old-style text index"
+            ),
+            IndexError::UnsupportedVersion(v) => write!(f, "unsupported index version {}", v),
+            IndexError::Truncated => write!(f, "index file is truncated"),
+            IndexError::ChecksumMismatch => write!(f, "index checksum mismatch"),
+            IndexError::InvalidStage { path, stage } => {
+                write!(f, "invalid stage {} for {:?}", stage, path)
+            }
+            IndexError::InvalidPath(v) => write!(f, "invalid path {:?}", v),
+            IndexError::Unsorted(v) => write!(f, "entry {:?} is out of order", v),
+        }
+    }
+}
+
+/// Whether `bytes` hold the old `path||hash` text index, written before the
+/// binary format existed. Only text whose every line parses counts, so a
+/// damaged binary index is reported by `decode` instead.
+pub fn is_legacy(bytes: &[u8]) -> bool {
+    if bytes.starts_with(INDEX_MAGIC) {
+        return false;
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => decode_legacy(text).1.is_empty(),
+        Err(_e) => false,
+    }
+}
+
+/// Serializes the entries as `KVCI`, version and entry count (big endian),
+/// then each entry sorted by path and stage, then a SHA-256 of all of it.
+///
+/// Each entry is a flags byte, the stage, the raw hash, the stat fields
+/// (zeroed when the flags say there are none), the path length and the path.
+pub fn encode(mut entries: Vec<IndexEntry>) -> Vec<u8> {
+    entries.sort_by(|a, b| (&a.path, a.stage).cmp(&(&b.path, b.stage)));
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(INDEX_MAGIC);
+    bytes.extend_from_slice(&INDEX_VERSION.to_be_bytes());
+    bytes.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+
+    for entry in &entries {
+        let flags = match entry.stat {
+            Some(_) => HAS_STAT_FLAG,
+            None => 0,
+        };
+        let stat = entry.stat.unwrap_or(FileStat {
+            size: 0,
+            mtime: 0,
+            mtime_nsec: 0,
+            ctime: 0,
+            ctime_nsec: 0,
+            inode: 0,
+            mode: 0,
+        });
+
+        bytes.push(flags);
+        bytes.push(entry.stage);
+        bytes.extend_from_slice(&hash_to_bytes(&entry.hash));
+        bytes.extend_from_slice(&stat.size.to_be_bytes());
+        bytes.extend_from_slice(&stat.mtime.to_be_bytes());
+        bytes.extend_from_slice(&stat.mtime_nsec.to_be_bytes());
+        bytes.extend_from_slice(&stat.ctime.to_be_bytes());
+        bytes.extend_from_slice(&stat.ctime_nsec.to_be_bytes());
+        bytes.extend_from_slice(&stat.inode.to_be_bytes());
+        bytes.extend_from_slice(&stat.mode.to_be_bytes());
+        bytes.extend_from_slice(&(entry.path.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(entry.path.as_bytes());
+    }
+
+    let checksum = Sha256::digest(&bytes);
+    bytes.extend_from_slice(&checksum);
+
+    bytes
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Vec<IndexEntry>, IndexError> {
+    if !bytes.starts_with(INDEX_MAGIC) {
+        return Err(IndexError::UnknownFormat);
+    }
+
+    if bytes.len() < HEADER_SIZE + CHECKSUM_SIZE {
+        return Err(IndexError::Truncated);
+    }
+
+    let (content, checksum) = bytes.split_at(bytes.len() - CHECKSUM_SIZE);
+    if Sha256::digest(content)[..] != *checksum {
+        return Err(IndexError::ChecksumMismatch);
+    }
+
+    let mut reader = Reader {
+        bytes: content,
+        offset: INDEX_MAGIC.len(),
+    };
+
+    let version = reader.u32()?;
+    if version != INDEX_VERSION {
+        return Err(IndexError::UnsupportedVersion(version));
+    }
+
+    let count = reader.u32()?;
+    let mut entries: Vec<IndexEntry> = Vec::new();
+
+    for _ in 0..count {
+        let flags = reader.take(1)?[0];
+        let stage = reader.take(1)?[0];
+        let hash = bytes_to_hash(reader.take(HASH_SIZE)?);
+        let stat = FileStat {
+            size: reader.u64()?,
+            mtime: reader.u64()? as i64,
+            mtime_nsec: reader.u64()? as i64,
+            ctime: reader.u64()? as i64,
+            ctime_nsec: reader.u64()? as i64,
+            inode: reader.u64()?,
+            mode: reader.u32()?,
+        };
+
+        let path_len = reader.u32()? as usize;
+        let path = match String::from_utf8(reader.take(path_len)?.to_vec()) {
+            Ok(v) => v,
+            Err(e) => return Err(IndexError::InvalidPath(e.to_string())),
+        };
+
+        if path.is_empty() || path.contains('\0') || path.starts_with('/') {
+            return Err(IndexError::InvalidPath(path));
+        }
+
+        if stage > 3 || (stage != 0 && flags & HAS_STAT_FLAG != 0) {
+            return Err(IndexError::InvalidStage { path, stage });
+        }
+
+        if let Some(previous) = entries.last() {
+            if (&previous.path, previous.stage) >= (&path, stage) {
+                return Err(IndexError::Unsorted(path));
+            }
+        }
+
+        entries.push(IndexEntry {
+            path,
+            stage,
+            hash,
+            stat: (flags & HAS_STAT_FLAG != 0).then_some(stat),
+        });
+    }
+
+    if reader.offset != content.len() {
+        return Err(IndexError::Truncated);
+    }
+
+    Ok(entries)
+}
+
+/// Reads the old text index: `path||hash`, `path||hash||0||<stat>` or
+/// `path||hash||<stage>` lines. Lines that can't be parsed are returned as
+/// `(line number, content)` instead.
+pub fn decode_legacy(content: &str) -> (Vec<IndexEntry>, Vec<(usize, String)>) {
+    let mut entries = Vec::new();
+    let mut malformed = Vec::new();
+
+    let content = content.strip_suffix('\n').unwrap_or(content);
+    if content.is_empty() {
+        return (entries, malformed);
+    }
+
+    for (idx, line) in content.split('\n').enumerate() {
+        match parse_legacy_line(line) {
+            Some(v) => entries.push(v),
+            None => malformed.push((idx + 1, line.to_owned())),
+        }
+    }
+
+    (entries, malformed)
+}
+
+fn parse_legacy_line(line: &str) -> Option<IndexEntry> {
+    let (path, value) = line.split_once("||")?;
+    let mut fields = value.splitn(3, "||");
+
+    let hash = fields.next()?;
+    let stage: u8 = fields.next().unwrap_or("0").parse().ok()?;
+    let stat = match fields.next() {
+        Some(v) if stage == 0 => Some(FileStat::parse(v)?),
+        Some(_) => return None,
+        None => None,
+    };
+
+    let is_valid = !path.is_empty()
+        && stage <= 3
+        && hash.len() == 64
+        && hash.chars().all(|v| v.is_ascii_hexdigit());
+
+    is_valid.then(|| IndexEntry {
+        path: path.to_owned(),
+        stage,
+        hash: hash.to_owned(),
+        stat,
+    })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], IndexError> {
+        let end = self.offset.checked_add(len).ok_or(IndexError::Truncated)?;
+        let slice = self
+            .bytes
+            .get(self.offset..end)
+            .ok_or(IndexError::Truncated)?;
+        self.offset = end;
+
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32, IndexError> {
+        let bytes = self.take(4)?;
+
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, IndexError> {
+        let mut value = [0_u8; 8];
+        value.copy_from_slice(self.take(8)?);
+
+        Ok(u64::from_be_bytes(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRY_SIZE: usize = 2 + HASH_SIZE + 6 * 8 + 4 + 4;
+
+    fn entry(path: &str, stage: u8, hash_digit: char, stat: Option<FileStat>) -> IndexEntry {
+        IndexEntry {
+            path: path.to_owned(),
+            stage,
+            hash: hash_digit.to_string().repeat(64),
+            stat,
+        }
+    }
+
+    fn stat() -> FileStat {
+        FileStat {
+            size: 42,
+            mtime: 1_700_000_000,
+            mtime_nsec: 123,
+            ctime: -5,
+            ctime_nsec: 456,
+            inode: 7,
+            mode: 0o100644,
+        }
+    }
+
+    /// Replaces the trailing checksum with the one of the modified content.
+    fn reseal(bytes: &mut Vec<u8>) {
+        bytes.truncate(bytes.len() - CHECKSUM_SIZE);
+        let checksum = Sha256::digest(&bytes[..]);
+        bytes.extend_from_slice(&checksum);
+    }
+
+    #[test]
+    fn decode_reads_back_encoded_entries_in_order() {
+        let bytes = encode(vec![
+            entry("src/main.rs", 0, 'a', Some(stat())),
+            entry("b", 3, 'c', None),
+            entry("b", 1, 'b', None),
+            entry("a", 0, 'd', None),
+        ]);
+
+        let entries = decode(&bytes).unwrap();
+
+        let keys: Vec<(&str, u8)> = entries.iter().map(|v| (&v.path[..], v.stage)).collect();
+        assert_eq!(keys, vec![("a", 0), ("b", 1), ("b", 3), ("src/main.rs", 0)]);
+        assert_eq!(entries[1].hash, "b".repeat(64));
+        assert_eq!(entries[0].stat, None);
+        assert_eq!(entries[3].stat, Some(stat()));
+    }
+
+    #[test]
+    fn decode_reads_an_empty_index() {
+        assert!(decode(&encode(Vec::new())).unwrap().is_empty());
+    }
+
+    #[test]
+    fn decode_rejects_a_checksum_mismatch() {
+        let mut bytes = encode(vec![entry("a", 0, 'a', None)]);
+        bytes[HEADER_SIZE + 2] ^= 1;
+
+        assert!(matches!(decode(&bytes), Err(IndexError::ChecksumMismatch)));
+    }
+
+    #[test]
+    fn decode_rejects_unsorted_entries() {
+        let mut bytes = encode(vec![entry("a", 0, 'a', None), entry("b", 0, 'b', None)]);
+        let (first, second) = bytes[HEADER_SIZE..].split_at_mut(ENTRY_SIZE + 1);
+        first.swap_with_slice(&mut second[..ENTRY_SIZE + 1]);
+        reseal(&mut bytes);
+
+        assert!(matches!(decode(&bytes), Err(IndexError::Unsorted(v)) if v == "a"));
+    }
+
+    #[test]
+    fn decode_rejects_duplicate_entries() {
+        let mut bytes = encode(vec![entry("a", 0, 'a', None), entry("b", 0, 'b', None)]);
+        let second_path = HEADER_SIZE + 2 * ENTRY_SIZE + 1;
+        bytes[second_path] = b'a';
+        reseal(&mut bytes);
+
+        assert!(matches!(decode(&bytes), Err(IndexError::Unsorted(v)) if v == "a"));
+    }
+
+    #[test]
+    fn decode_rejects_truncated_and_unknown_files() {
+        let bytes = encode(vec![entry("a", 0, 'a', None)]);
+
+        assert!(matches!(
+            decode(&bytes[..HEADER_SIZE]),
+            Err(IndexError::Truncated)
+        ));
+        assert!(matches!(decode(b"a||b\n"), Err(IndexError::UnknownFormat)));
+    }
+
+    #[test]
+    fn is_legacy_only_accepts_valid_text_indexes() {
+        let line = format!("src/main.rs||{}\n", "a".repeat(64));
+
+        assert!(is_legacy(line.as_bytes()));
+        assert!(is_legacy(b""));
+        assert!(!is_legacy(b"not an index\n"));
+        assert!(!is_legacy(&[0xff, 0xfe, b'\n']));
+        assert!(!is_legacy(&encode(vec![entry("a", 0, 'a', None)])));
+    }
+}
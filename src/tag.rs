@@ -0,0 +1,78 @@
+use std::{fmt, path};
+
+use crate::{
+    commit::Signature,
+    object::{read_object, ObjectKind},
+};
+
+pub struct Tag {
+    pub object: String,
+    pub kind: ObjectKind,
+    pub name: String,
+    pub tagger: Signature,
+    pub message: String,
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "object {}", self.object)?;
+        writeln!(f, "type {}", self.kind)?;
+        writeln!(f, "tag {}", self.name)?;
+        writeln!(f, "tagger {}", self.tagger)?;
+        writeln!(f)?;
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Tag {
+    pub fn parse(content: &str) -> Option<Tag> {
+        let (headers, message) = content.split_once("\n\n").unwrap_or((content, ""));
+
+        let mut object = None;
+        let mut kind = None;
+        let mut name = None;
+        let mut tagger = None;
+
+        for line in headers.lines() {
+            let (key, value) = line.split_once(' ')?;
+
+            match key {
+                "object" => object = Some(value.to_owned()),
+                "type" => kind = ObjectKind::parse(value),
+                "tag" => name = Some(value.to_owned()),
+                "tagger" => tagger = Signature::parse(value),
+                _ => continue,
+            }
+        }
+
+        Some(Tag {
+            object: object?,
+            kind: kind?,
+            name: name?,
+            tagger: tagger?,
+            message: message.to_owned(),
+        })
+    }
+
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or_default()
+    }
+
+    /// Follows annotated tags until reaching an object that is not a tag.
+    pub fn peel(root_path: &path::Path, hash: &str) -> String {
+        let mut current = hash.to_owned();
+
+        while let Ok(object) = read_object(root_path, &current) {
+            if object.kind != ObjectKind::Tag {
+                break;
+            }
+
+            match Tag::parse(&object.text()) {
+                Some(tag) => current = tag.object,
+                None => break,
+            }
+        }
+
+        current
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path,
+};
+
+use crate::object::{read_object_of_kind, write_object, ObjectKind};
+
+pub struct TreeEntry {
+    pub kind: ObjectKind,
+    pub hash: String,
+    pub name: String,
+}
+
+enum TreeNode {
+    Blob(String),
+    Tree(BTreeMap<String, TreeNode>),
+}
+
+pub fn write_tree_from_index(root_path: &path::Path, entries: &HashMap<String, String>) -> String {
+    let mut root: BTreeMap<String, TreeNode> = BTreeMap::new();
+
+    for (file_path, hash) in entries {
+        let components: Vec<&str> = file_path
+            .split('/')
+            .filter(|v| !v.is_empty() && *v != ".")
+            .collect();
+
+        insert_node(&mut root, &components, hash);
+    }
+
+    write_node(root_path, &root)
+}
+
+fn insert_node(node: &mut BTreeMap<String, TreeNode>, components: &[&str], hash: &str) {
+    let (name, rest) = match components.split_first() {
+        Some(v) => v,
+        None => return,
+    };
+
+    if rest.is_empty() {
+        node.insert((*name).to_owned(), TreeNode::Blob(hash.to_owned()));
+        return;
+    }
+
+    let child = node
+        .entry((*name).to_owned())
+        .or_insert_with(|| TreeNode::Tree(BTreeMap::new()));
+
+    if let TreeNode::Blob(_) = child {
+        *child = TreeNode::Tree(BTreeMap::new());
+    }
+
+    if let TreeNode::Tree(children) = child {
+        insert_node(children, rest, hash);
+    }
+}
+
+/// Tree entries are stored one per line as `<kind> <hash>\t<name>`, so a
+/// path with a newline or a tab in it can't be written to a tree.
+pub fn is_storable_path(file_path: &str) -> bool {
+    !file_path.contains(['\n', '\t'])
+}
+
+fn write_node(root_path: &path::Path, node: &BTreeMap<String, TreeNode>) -> String {
+    let mut tree_content = String::new();
+
+    for (name, child) in node {
+        let line = match child {
+            TreeNode::Blob(hash) => format!("blob {}\t{}\n", hash, name),
+            TreeNode::Tree(children) => {
+                format!("tree {}\t{}\n", write_node(root_path, children), name)
+            }
+        };
+
+        tree_content.push_str(&line);
+    }
+
+    write_object(root_path, ObjectKind::Tree, tree_content.as_bytes())
+}
+
+pub fn parse_tree(content: &str) -> Option<Vec<TreeEntry>> {
+    let mut entries = Vec::new();
+
+    for line in content.lines() {
+        let (header, name) = line.split_once('\t')?;
+        let (kind, hash) = header.split_once(' ')?;
+
+        entries.push(TreeEntry {
+            kind: ObjectKind::parse(kind)?,
+            hash: hash.to_owned(),
+            name: name.to_owned(),
+        });
+    }
+
+    Some(entries)
+}
+
+pub fn read_tree(root_path: &path::Path, tree_hash: &str) -> Vec<TreeEntry> {
+    let tree = match read_object_of_kind(root_path, tree_hash, ObjectKind::Tree) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not read tree {}: {}", tree_hash, e),
+    };
+
+    match parse_tree(&tree.text()) {
+        Some(v) => v,
+        None => panic!("The tree {} is malformed!", tree_hash),
+    }
+}
+
+pub fn flatten_tree(root_path: &path::Path, tree_hash: &str) -> HashMap<String, String> {
+    let mut files = HashMap::new();
+
+    collect_tree_files(root_path, tree_hash, "", &mut files);
+
+    files
+}
+
+fn collect_tree_files(
+    root_path: &path::Path,
+    tree_hash: &str,
+    prefix: &str,
+    files: &mut HashMap<String, String>,
+) {
+    for entry in read_tree(root_path, tree_hash) {
+        let file_path = match prefix.is_empty() {
+            true => entry.name,
+            false => format!("{}/{}", prefix, entry.name),
+        };
+
+        match entry.kind {
+            ObjectKind::Tree => collect_tree_files(root_path, &entry.hash, &file_path, files),
+            _ => {
+                files.insert(file_path, entry.hash);
+            }
+        }
+    }
+}
+
+pub fn find_path_hash(root_path: &path::Path, tree_hash: &str, file_path: &str) -> Option<String> {
+    let mut current_hash = tree_hash.to_owned();
+
+    for component in file_path.split('/').filter(|v| !v.is_empty() && *v != ".") {
+        let entries = read_tree(root_path, &current_hash);
+        let entry = entries.into_iter().find(|v| v.name == component)?;
+
+        current_hash = entry.hash;
+    }
+
+    Some(current_hash)
+}
@@ -3,7 +3,6 @@ use std::{
     io::{BufReader, Error, Read},
     os::unix::fs::MetadataExt,
     path, process,
-    str::from_utf8,
 };
 
 use sha2::{Digest, Sha256};
@@ -13,6 +12,12 @@ use crate::constants::{MINIMUN_LARGE_FILE_SIZE, ROOT_FOLDER_NAME};
 pub fn validate_path(path: String) -> bool {
     let is_path_valid = fs::exists(&path).unwrap_or_default();
 
+    is_path_valid && is_inside_current_dir(&path)
+}
+
+/// Whether `path` points somewhere under the current directory, without
+/// requiring it to exist (e.g. a tracked file that was deleted).
+pub fn is_inside_current_dir(path: &str) -> bool {
     let cur_dir = get_current_dir();
     let cur_dir_abs_path = match path::absolute(&cur_dir) {
         Ok(v) => v,
@@ -22,7 +27,7 @@ pub fn validate_path(path: String) -> bool {
         }
     };
 
-    let args_abs_path = match path::absolute(&path) {
+    let args_abs_path = match path::absolute(path) {
         Ok(v) => v,
         Err(_) => {
             println!("Something went wrong converting to absolute args path!");
@@ -30,14 +35,10 @@ pub fn validate_path(path: String) -> bool {
         }
     };
 
-    if !is_path_valid || !args_abs_path.starts_with(cur_dir_abs_path.to_str().unwrap()) {
-        return false;
-    }
-
-    return true;
+    args_abs_path.starts_with(cur_dir_abs_path.to_str().unwrap())
 }
 
-pub fn read_file(path: &str) -> String {
+pub fn read_file(path: &str) -> Vec<u8> {
     let file_size = check_file_size(path);
 
     if file_size > MINIMUN_LARGE_FILE_SIZE {
@@ -47,7 +48,7 @@ pub fn read_file(path: &str) -> String {
     read_tiny_file(path)
 }
 
-fn check_file_size(path: &str) -> u64 {
+pub fn check_file_size(path: &str) -> u64 {
     let file_metadata = fs::metadata(path).unwrap();
 
     file_metadata.size()
@@ -59,9 +60,7 @@ pub fn get_current_dir() -> path::PathBuf {
         Err(e) => panic!("something went wrong: {}", e),
     };
 
-    let root_dir_path = path::PathBuf::from(&cur_dir);
-
-    root_dir_path
+    path::PathBuf::from(&cur_dir)
 }
 
 pub fn get_kvc_root_folder(path: path::PathBuf) -> (bool, path::PathBuf) {
@@ -69,11 +68,7 @@ pub fn get_kvc_root_folder(path: path::PathBuf) -> (bool, path::PathBuf) {
 
     cloned_path.extend(&[ROOT_FOLDER_NAME]);
 
-    loop {
-        if fs::exists(&cloned_path).expect("something went wrong!") {
-            break;
-        }
-
+    if !fs::exists(&cloned_path).expect("something went wrong!") {
         cloned_path = path.clone();
         let popped = cloned_path.pop();
         if !popped {
@@ -103,57 +98,71 @@ pub fn get_file_path_relative_to_root(root_path: path::PathBuf, path: String) ->
         Err(e) => panic!("Could not strip file prefix! {}", e),
     };
 
-    if root_comp_path == "" {
-        format!("{}", path)
-    } else {
-        format!("{}/{}", root_comp_path, path)
+    let joined = match root_comp_path.is_empty() {
+        true => path,
+        false => format!("{}/{}", root_comp_path, path),
+    };
+
+    normalize_relative_path(&joined)
+}
+
+/// Drops `.` components and resolves `..` so the same file always gets the
+/// same key in the index, e.g. `./src/../a.txt` becomes `a.txt`.
+pub fn normalize_relative_path(path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+
+    for component in path.split('/') {
+        match component {
+            "" | "." => continue,
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
     }
+
+    components.join("/")
 }
 
-pub fn generate_hash(file_content: &str) -> Result<String, Error> {
+pub fn generate_hash(file_content: &[u8]) -> Result<String, Error> {
     let result = Sha256::digest(file_content);
     let result_str = format!("{:x}", result);
 
     Ok(result_str)
 }
 
-fn read_tiny_file(path: &str) -> String {
+fn read_tiny_file(path: &str) -> Vec<u8> {
     let mut file_input = match fs::File::open(path) {
         Ok(v) => v,
         Err(_e) => panic!("Could not read file!"),
     };
 
-    let mut read_file = String::new();
-    file_input
-        .read_to_string(&mut read_file)
-        .unwrap_or_default();
+    let mut read_file = Vec::new();
+    match file_input.read_to_end(&mut read_file) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not read file {}: {}", path, e),
+    };
 
     read_file
 }
 
-fn read_large_file(path: &str) -> String {
+fn read_large_file(path: &str) -> Vec<u8> {
     let file_input = fs::File::open(path).unwrap();
     let mut reader = BufReader::new(file_input);
     let mut buffer = [0_u8; 1024];
 
-    let mut read_file = String::new();
+    let mut read_file = Vec::new();
     loop {
-        let count = reader.read(&mut buffer).unwrap();
-
-        if count == 0 {
-            break;
-        }
-
-        let read_slice = match from_utf8(&buffer[..count]) {
-            Ok(v) => v.to_owned(),
-            Err(_e) => "".to_owned(),
+        let count = match reader.read(&mut buffer) {
+            Ok(v) => v,
+            Err(e) => panic!("Could not read file {}: {}", path, e),
         };
 
-        if read_slice == "" {
+        if count == 0 {
             break;
         }
 
-        read_file = format!("{}{}", read_file, read_slice);
+        read_file.extend_from_slice(&buffer[..count]);
     }
 
     read_file
@@ -162,3 +171,79 @@ fn read_large_file(path: &str) -> String {
 pub fn segment_hash(hash: &str) -> (&str, &str) {
     hash.split_at(2)
 }
+
+/// Matches `text` against a shell-like pattern supporting `*`, `?` and
+/// `[...]` classes (with `!` or `^` negation and `a-z` ranges).
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    glob_match_chars(&pattern, &text)
+}
+
+fn glob_match_chars(pattern: &[char], text: &[char]) -> bool {
+    let (first, rest) = match pattern.split_first() {
+        Some(v) => v,
+        None => return text.is_empty(),
+    };
+
+    match first {
+        '*' => (0..=text.len()).any(|idx| glob_match_chars(rest, &text[idx..])),
+        '?' => !text.is_empty() && glob_match_chars(rest, &text[1..]),
+        '[' => {
+            let class_end = match rest.iter().skip(1).position(|v| *v == ']') {
+                Some(v) => v + 1,
+                None => return text.first() == Some(first) && glob_match_chars(rest, &text[1..]),
+            };
+
+            match text.first() {
+                Some(v) if class_matches(&rest[..class_end], *v) => {
+                    glob_match_chars(&rest[class_end + 1..], &text[1..])
+                }
+                _ => false,
+            }
+        }
+        _ => text.first() == Some(first) && glob_match_chars(rest, &text[1..]),
+    }
+}
+
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!') | Some('^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+
+    let mut matched = false;
+    let mut idx = 0;
+
+    while idx < class.len() {
+        if idx + 2 < class.len() && class[idx + 1] == '-' {
+            matched |= class[idx] <= c && c <= class[idx + 2];
+            idx += 3;
+            continue;
+        }
+
+        matched |= class[idx] == c;
+        idx += 1;
+    }
+
+    matched != negated
+}
+
+/// Packs a hex SHA-256 hash into its 32 raw bytes.
+pub fn hash_to_bytes(hash: &str) -> [u8; 32] {
+    let mut bytes = [0_u8; 32];
+
+    for (idx, byte) in bytes.iter_mut().enumerate() {
+        *byte = hash
+            .get(idx * 2..idx * 2 + 2)
+            .and_then(|v| u8::from_str_radix(v, 16).ok())
+            .unwrap_or_default();
+    }
+
+    bytes
+}
+
+pub fn bytes_to_hash(bytes: &[u8]) -> String {
+    bytes.iter().map(|v| format!("{:02x}", v)).collect()
+}